@@ -110,10 +110,7 @@ fn all_intersections(
 fn closest_intersection(
     wires: impl IntoIterator<Item = impl IntoIterator<Item = Direction>>,
 ) -> Option<i32> {
-    all_intersections(wires)
-        .into_iter()
-        .map(|(_, dist)| dist)
-        .min()
+    all_intersections(wires).into_values().min()
 }
 
 #[test]
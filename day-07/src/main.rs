@@ -1,46 +1,28 @@
-use std::{iter, thread};
-
 pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 fn amplifier(program: &intcode::Program, phase: &[intcode::Byte]) -> Result<intcode::Byte> {
-    // T0R0 T1R1 T2R2 T3R3 T4R4
-    let (mut txs, rxs): (Vec<_>, Vec<_>) = iter::repeat_with(intcode::channel)
-        .take(phase.len())
-        .unzip();
-
-    let tx = txs.first().unwrap().clone();
-    let rx = rxs.first().unwrap().clone();
-
-    // Load phase while tx/rx are still in sync
-    for (tx, &phase_digit) in txs.iter().zip(phase) {
-        tx.send(phase_digit).expect("Unable to load phase digit");
-    }
-
-    // T1R0 T2R1 T3R2 T4R3 T0R4
-    txs.rotate_left(1);
-
-    // <T1R0> A0; <T2R1> A1; <T3R2> A2; <T4R3> A3; <T0R4> A4
-    let amps: Vec<_> = txs
-        .into_iter()
-        .zip(rxs)
-        .map(|(tx, rx)| {
-            let mut program = program.to_owned();
-
-            thread::spawn(move || intcode::execute_with_output(&mut program, rx, tx))
+    let mut pipeline = intcode::Pipeline::new();
+
+    let amps: Vec<_> = phase
+        .iter()
+        .map(|&phase_digit| {
+            let amp = pipeline.add(program.clone());
+            pipeline.input(amp, phase_digit);
+            amp
         })
         .collect();
+    let first = *amps.first().unwrap();
+    let last = *amps.last().unwrap();
 
-    // Send initial value
-    tx.send(0).map_err(Error::from)?;
-    drop(tx);
-
-    amps.into_iter()
-        .map(|t| t.join().expect("Thread panicked"))
-        .collect::<Result<Vec<()>, _>>()?;
+    pipeline.input(first, 0);
+    pipeline.feedback_loop(&amps);
 
-    // Get last value
-    rx.recv().map_err(Into::into)
+    let outputs = pipeline.run(&[last])?;
+    let value = outputs[0]
+        .last()
+        .ok_or("The last amplifier produced no output")?;
+    Ok(*value)
 }
 
 enum SearchSpace {
@@ -61,6 +43,21 @@ fn search_for_max(program: &intcode::Program, space: SearchSpace) -> Result<Opti
     )
 }
 
+const INPUT: &str = include_str!("input.txt");
+
+fn main() -> Result<()> {
+    let program = INPUT
+        .trim()
+        .split(",")
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!("{:?}", search_for_max(&program, SearchSpace::Plain));
+    println!("{:?}", search_for_max(&program, SearchSpace::Feedback));
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -82,17 +79,17 @@ mod test {
     #[test]
     fn amplifier_functionality() -> Result<()> {
         assert_eq!(
-            amplifier(&mut EXAMPLE_PROGRAM_1.to_vec(), &[4, 3, 2, 1, 0])?,
+            amplifier(&EXAMPLE_PROGRAM_1.to_vec(), &[4, 3, 2, 1, 0])?,
             43210
         );
 
         assert_eq!(
-            amplifier(&mut EXAMPLE_PROGRAM_2.to_vec(), &[0, 1, 2, 3, 4])?,
+            amplifier(&EXAMPLE_PROGRAM_2.to_vec(), &[0, 1, 2, 3, 4])?,
             54321
         );
 
         assert_eq!(
-            amplifier(&mut EXAMPLE_PROGRAM_3.to_vec(), &[1, 0, 4, 3, 2])?,
+            amplifier(&EXAMPLE_PROGRAM_3.to_vec(), &[1, 0, 4, 3, 2])?,
             65210
         );
 
@@ -104,17 +101,17 @@ mod test {
         use SearchSpace::*;
 
         assert_eq!(
-            search_for_max(&mut EXAMPLE_PROGRAM_1.to_vec(), Plain)?,
+            search_for_max(&EXAMPLE_PROGRAM_1.to_vec(), Plain)?,
             Some(43210)
         );
 
         assert_eq!(
-            search_for_max(&mut EXAMPLE_PROGRAM_2.to_vec(), Plain)?,
+            search_for_max(&EXAMPLE_PROGRAM_2.to_vec(), Plain)?,
             Some(54321)
         );
 
         assert_eq!(
-            search_for_max(&mut EXAMPLE_PROGRAM_3.to_vec(), Plain)?,
+            search_for_max(&EXAMPLE_PROGRAM_3.to_vec(), Plain)?,
             Some(65210)
         );
 
@@ -135,12 +132,12 @@ mod test {
     #[test]
     fn amplifier_feedback_functionality() -> Result<()> {
         assert_eq!(
-            amplifier(&mut FEEDBACK_PROGRAM_1.to_vec(), &[9, 8, 7, 6, 5])?,
+            amplifier(&FEEDBACK_PROGRAM_1.to_vec(), &[9, 8, 7, 6, 5])?,
             139629729
         );
 
         assert_eq!(
-            amplifier(&mut FEEDBACK_PROGRAM_2.to_vec(), &[9, 7, 8, 5, 6])?,
+            amplifier(&FEEDBACK_PROGRAM_2.to_vec(), &[9, 7, 8, 5, 6])?,
             18216
         );
 
@@ -152,30 +149,15 @@ mod test {
         use SearchSpace::*;
 
         assert_eq!(
-            search_for_max(&mut FEEDBACK_PROGRAM_1.to_vec(), Feedback)?,
+            search_for_max(&FEEDBACK_PROGRAM_1.to_vec(), Feedback)?,
             Some(139629729)
         );
 
         assert_eq!(
-            search_for_max(&mut FEEDBACK_PROGRAM_2.to_vec(), Feedback)?,
+            search_for_max(&FEEDBACK_PROGRAM_2.to_vec(), Feedback)?,
             Some(18216)
         );
 
         Ok(())
     }
 }
-
-const INPUT: &str = include_str!("input.txt");
-
-fn main() -> Result<()> {
-    let program = INPUT
-        .trim()
-        .split(",")
-        .map(str::parse)
-        .collect::<Result<Vec<_>, _>>()?;
-
-    println!("{:?}", search_for_max(&program, SearchSpace::Plain));
-    println!("{:?}", search_for_max(&program, SearchSpace::Feedback));
-
-    Ok(())
-}
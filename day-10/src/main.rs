@@ -51,8 +51,8 @@ impl Map {
     fn vaporization_order(&self, coord: Coord) -> impl Iterator<Item = Coord> {
         let mut lines: VecDeque<_> = self
             .lines_of_sight(coord)
-            .into_iter()
-            .map(|(_, mut line)| {
+            .into_values()
+            .map(|mut line| {
                 line.sort_by_key(|a| a.manhattan_distance());
                 // Want the closest values at the end of the array for
                 // easier popping
@@ -159,6 +159,24 @@ fn gcd(a: Dim, b: Dim) -> Dim {
     }
 }
 
+const INPUT: &str = include_str!("input.txt");
+
+fn main() -> Result<()> {
+    let map: Map = INPUT.parse()?;
+    let (station_coord, _) = map.maximum_visible()?;
+
+    let asteroid_coord = map
+        .vaporization_order(station_coord)
+        .nth(199)
+        .ok_or("Not enough vaporized")?;
+
+    println!("{}", asteroid_coord.0 * 100 + asteroid_coord.1);
+
+    // Wrong: 705 (off-by-one; 200 -> 199)
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -343,21 +361,3 @@ mod test {
         Ok(())
     }
 }
-
-const INPUT: &str = include_str!("input.txt");
-
-fn main() -> Result<()> {
-    let map: Map = INPUT.parse()?;
-    let (station_coord, _) = map.maximum_visible()?;
-
-    let asteroid_coord = map
-        .vaporization_order(station_coord)
-        .nth(199)
-        .ok_or("Not enough vaporized")?;
-
-    println!("{}", asteroid_coord.0 * 100 + asteroid_coord.1);
-
-    // Wrong: 705 (off-by-one; 200 -> 199)
-
-    Ok(())
-}
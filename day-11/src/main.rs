@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::collections::BTreeMap;
+use std::{cell::RefCell, collections::BTreeMap};
 
 const BLACK: intcode::Byte = 0;
 const WHITE: intcode::Byte = 1;
@@ -46,48 +46,45 @@ impl Direction {
     }
 }
 
-fn painted_squares(mut program: intcode::Program) -> usize {
-    paint_common(&mut program, BLACK).len()
+fn painted_squares(program: intcode::Program) -> usize {
+    paint_common(program, BLACK).len()
 }
 
-fn painted_hull(mut program: intcode::Program) -> Hull {
-    paint_common(&mut program, WHITE)
+fn painted_hull(program: intcode::Program) -> Hull {
+    paint_common(program, WHITE)
 }
 
-fn paint_common(program: &mut intcode::Program, initial_square: intcode::Byte) -> Hull {
-    intcode::execute_side_by_side(program, move |tx, rx| {
-        use Direction::*;
-
-        let mut hull = BTreeMap::new();
-        let mut position = (0, 0);
-        let mut direction = Up;
+fn paint_common(program: intcode::Program, initial_square: intcode::Byte) -> Hull {
+    let mut hull = BTreeMap::new();
+    hull.insert((0, 0), initial_square);
 
-        hull.insert(position, initial_square);
+    let hull = RefCell::new(hull);
+    let robot = RefCell::new(((0, 0), Direction::Up));
 
-        let mut rx = rx.into_iter().tuples();
+    let mut camera = || {
+        let (position, _) = *robot.borrow();
+        let color = hull.borrow().get(&position).copied().unwrap_or(BLACK);
+        intcode::Input::Value(color)
+    };
 
-        loop {
-            let color = hull.get(&position).copied().unwrap_or(BLACK);
-            tx.send(color).expect("Computer has unexpectedly shut down");
+    let motor = intcode::Chunks::new(|(color, turn_direction)| {
+        let (position, direction) = &mut *robot.borrow_mut();
+        hull.borrow_mut().insert(*position, color);
+        *direction = direction.turn(turn_direction);
+        *position = direction.move_forward_from(*position);
+    });
 
-            match rx.next() {
-                Some((color, turn_direction)) => {
-                    hull.insert(position, color);
-                    direction = direction.turn(turn_direction);
-                    position = direction.move_forward_from(position);
-                }
-                None => break,
-            }
-        }
+    intcode::Computer::new(program)
+        .execute_until_blocked(&mut camera, motor)
+        .expect("Unable to paint the hull");
 
-        hull
-    })
+    hull.into_inner()
 }
 
 const INPUT: &str = include_str!("input.txt");
 
 fn main() {
-    let program = intcode::parse_program(INPUT);
+    let program = intcode::parse_program(INPUT).expect("Unable to parse program");
 
     let painted_squares = painted_squares(program.clone());
     println!("{}", painted_squares);
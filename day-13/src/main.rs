@@ -1,5 +1,4 @@
-use itertools::Itertools;
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::{cell::RefCell, collections::BTreeMap, convert::TryFrom};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum Tile {
@@ -63,62 +62,72 @@ fn print_board(board: &Board) {
 }
 
 fn setup_game(computer: &mut intcode::Computer) -> Board {
-    computer.execute_side_by_side(|_tx, rx| {
-        let mut board = Board::new();
-        for (x, y, tile) in rx.into_iter().tuples() {
-            let tile = Tile::try_from(tile).expect("invalid tile");
-            board.insert((x, y), tile);
-        }
-        board
-    })
+    let mut board = Board::new();
+
+    let screen = intcode::Chunks::new(|(x, y, tile)| {
+        let tile = Tile::try_from(tile).expect("invalid tile");
+        board.insert((x, y), tile);
+    });
+    computer
+        .execute(None, screen)
+        .expect("Unable to set up the game");
+
+    board
 }
 
 const LEFT: intcode::Byte = -1;
 const NEUTRAL: intcode::Byte = 0;
 const RIGHT: intcode::Byte = 1;
 
-fn play_game(computer: &mut intcode::Computer, mut board: Board) -> intcode::Byte {
+fn find(board: &Board, tile: Tile) -> Option<intcode::Byte> {
+    board
+        .iter()
+        .find(|&(_, &t)| t == tile)
+        .map(|(&(x, _), _)| x)
+}
+
+fn play_game(computer: &mut intcode::Computer, board: Board) -> intcode::Byte {
     // Play for free
     computer.program[0] = 2;
 
-    computer.execute_side_by_side(move |tx, rx| {
-        let mut score = 0;
-
-        // Ignore errors - if the computer is shut down, we don't care.
-        let _ = tx.send(NEUTRAL);
-
-        for (i, (x, y, tile)) in rx.into_iter().tuples().enumerate() {
-            if x == -1 && y == 0 {
-                score = tile;
-            } else {
-                let tile = Tile::try_from(tile).expect("invalid tile");
-                board.insert((x, y), tile);
-
-                if tile == Tile::Ball {
-                    let ((px, _), _) = board
-                        .iter()
-                        .find(|&(_, &t)| t == Tile::HorizontalPaddle)
-                        .expect("No paddle");
-
-                    use std::cmp::Ordering::*;
-                    let paddle_direction = match x.cmp(&px) {
-                        Less => LEFT,
-                        Equal => NEUTRAL,
-                        Greater => RIGHT,
-                    };
-
-                    // Ignore errors - if the computer is shut down, we don't care.
-                    let _ = tx.send(paddle_direction);
-
-                    std::thread::sleep(std::time::Duration::from_millis(16));
-                    print_board(&board);
-                    println!("{}\t\t{}", i, score);
-                }
+    let board = RefCell::new(board);
+    let mut score = 0;
+
+    let mut joystick = || {
+        let board = board.borrow();
+        let ball = find(&board, Tile::Ball).expect("No ball");
+        let paddle = find(&board, Tile::HorizontalPaddle).expect("No paddle");
+
+        use std::cmp::Ordering::*;
+        intcode::Input::Value(match ball.cmp(&paddle) {
+            Less => LEFT,
+            Equal => NEUTRAL,
+            Greater => RIGHT,
+        })
+    };
+
+    let mut updates = 0;
+    let screen = intcode::Chunks::new(|(x, y, tile)| {
+        if x == -1 && y == 0 {
+            score = tile;
+        } else {
+            let tile = Tile::try_from(tile).expect("invalid tile");
+            board.borrow_mut().insert((x, y), tile);
+
+            if tile == Tile::Ball {
+                std::thread::sleep(std::time::Duration::from_millis(16));
+                print_board(&board.borrow());
+                println!("{}\t\t{}", updates, score);
             }
         }
+        updates += 1;
+    });
+
+    computer
+        .execute_until_blocked(&mut joystick, screen)
+        .expect("Unable to play the game");
 
-        score
-    })
+    score
 }
 
 const INPUT: &str = include_str!("input.txt");
@@ -126,7 +135,7 @@ const INPUT: &str = include_str!("input.txt");
 fn main() {
     let mut computer: intcode::Computer = INPUT.parse().expect("Unable to parse program");
 
-    let board = setup_game(&mut computer);
+    let board = setup_game(&mut computer.clone());
     let tiles = board.iter().filter(|&(_, &t)| t == Tile::Block).count();
     println!("{}", tiles);
 
@@ -185,6 +185,15 @@ fn rounding_up(a: u64, b: u64) -> u64 {
     }
 }
 
+const INPUT: &str = include_str!("input.txt");
+
+fn main() -> Result<()> {
+    let reactions: Reactions = INPUT.parse()?;
+    println!("{}", reactions.solve());
+    println!("{}", reactions.total());
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -288,12 +297,3 @@ mod test {
         Ok(())
     }
 }
-
-const INPUT: &str = include_str!("input.txt");
-
-fn main() -> Result<()> {
-    let reactions: Reactions = INPUT.parse()?;
-    println!("{}", reactions.solve());
-    println!("{}", reactions.total());
-    Ok(())
-}
@@ -148,19 +148,10 @@ fn map_out_area(computer: &mut intcode::Computer) -> Map {
         let mut rx = rx.into_iter();
 
         loop {
-            if position == (0, 0) && neighbors(position).all(|n| map.get(&n).is_some()) {
-                // We've returned to the start and visited everywhere
-                print_map(&map, (0, 0));
-
-                // The program never seems to halt, so we do work here
-                // and trigger our own panic to print out the data.
-                let path = calculate_path(&map);
-                println!("Found oxygen {} steps away", path.len() - 1);
-
-                let time = calculate_dispersion(map);
-                println!("Oxygen takes {} minutes to fill the area", time);
-
-                panic!("Program doesn't halt");
+            if position == (0, 0) && neighbors(position).all(|n| map.contains_key(&n)) {
+                // We've returned to the start and visited everywhere. The
+                // program never halts, so we stop it by returning.
+                return map;
             }
 
             let (direction, next_position) =
@@ -253,7 +244,15 @@ const INPUT: &str = include_str!("input.txt");
 
 fn main() -> Result<()> {
     let mut computer: intcode::Computer = INPUT.parse()?;
-    map_out_area(&mut computer);
+    let map = map_out_area(&mut computer);
+    print_map(&map, (0, 0));
+
+    let path = calculate_path(&map);
+    println!("Found oxygen {} steps away", path.len() - 1);
+
+    let time = calculate_dispersion(map);
+    println!("Oxygen takes {} minutes to fill the area", time);
+
     Ok(())
 
     // WRONG: 39
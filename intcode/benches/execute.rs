@@ -0,0 +1,78 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use intcode::{assemble, Computer, Program};
+
+/// The day 9 BOOST program, which spends most of its time in a
+/// recursive subroutine.
+const BOOST: &str = include_str!("../../day-09/src/input.txt");
+
+fn boost(c: &mut Criterion) {
+    let program = intcode::parse_program(BOOST).unwrap();
+
+    c.bench_function("boost", |b| {
+        b.iter(|| run(&program, 2, true));
+    });
+    c.bench_function("boost, uncached", |b| {
+        b.iter(|| run(&program, 2, false));
+    });
+}
+
+fn counting_loop(c: &mut Criterion) {
+    // Sums the numbers from `n` down to 1
+    let program = assemble(
+        "
+              arb  #100
+              in   [n]
+        loop: add  rb+0, [n], rb+0
+              add  [n], #-1, [n]
+              jt   [n], #loop
+              out  rb+0
+              hlt
+        n:    data 0
+        ",
+    )
+    .unwrap();
+
+    c.bench_function("counting loop", |b| {
+        b.iter(|| run(&program, 100_000, true));
+    });
+    c.bench_function("counting loop, uncached", |b| {
+        b.iter(|| run(&program, 100_000, false));
+    });
+}
+
+fn self_modifying(c: &mut Criterion) {
+    // Each pass overwrites the next instruction, without changing
+    // it, so that instruction is decoded again every time
+    let program = assemble(
+        "
+               in   [n]
+        loop:  add  #1001, #0, [op]
+        op:    add  [total], #1, [total]
+               add  [n], #-1, [n]
+               jt   [n], #loop
+               out  [total]
+               hlt
+        n:     data 0
+        total: data 0
+        ",
+    )
+    .unwrap();
+
+    c.bench_function("self-modifying loop", |b| {
+        b.iter(|| run(&program, 100_000, true));
+    });
+}
+
+/// Runs `program` with the single input `n`, decoding every
+/// instruction afresh unless `cached`, for comparison.
+fn run(program: &Program, n: i128, cached: bool) -> Vec<i128> {
+    let mut computer = Computer::new(program.clone());
+    computer.set_decode_cache(cached);
+
+    let mut output = Vec::new();
+    computer.execute(Some(n), &mut output).unwrap();
+    black_box(output)
+}
+
+criterion_group!(benches, boost, counting_loop, self_modifying);
+criterion_main!(benches);
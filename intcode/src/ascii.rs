@@ -0,0 +1,137 @@
+use crate::{Computer, Error, Io, Memory, Program, Word};
+use std::mem;
+
+/// What an ASCII program did next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Text<W> {
+    /// A line of output, without its newline. Output that isn't
+    /// followed by a newline is returned as a line once the program
+    /// outputs a value, asks for input or halts.
+    Line(String),
+    /// An output outside of the ASCII range, such as a puzzle answer.
+    Value(W),
+    NeedsInput,
+    Halted,
+}
+
+/// Talks to a program that prints text and reads commands.
+#[derive(Debug, Clone)]
+pub struct Ascii<M: Memory = Program> {
+    computer: Computer<M>,
+    line: String,
+    /// What to return after the partial line that came before it.
+    pending: Option<Text<M::Word>>,
+}
+
+impl<M> Ascii<M>
+where
+    M: Memory,
+{
+    pub fn new(computer: Computer<M>) -> Self {
+        Self {
+            computer,
+            line: String::new(),
+            pending: None,
+        }
+    }
+
+    pub fn computer(&self) -> &Computer<M> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer<M> {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Computer<M> {
+        self.computer
+    }
+
+    /// Queues each character of `command` followed by a newline.
+    pub fn send(&mut self, command: &str) {
+        for b in command.bytes().chain(Some(b'\n')) {
+            self.computer.push_input(M::Word::from_i64(b.into()));
+        }
+    }
+
+    /// Runs until the program finishes a line, outputs a value, needs
+    /// input or halts.
+    pub fn read(&mut self) -> Result<Text<M::Word>, Error<M::Word>> {
+        if let Some(text) = self.pending.take() {
+            return Ok(text);
+        }
+
+        loop {
+            let text = match self.computer.run_until_io()? {
+                Io::Output(v) => match v.to_usize().filter(|&c| c < 0x80) {
+                    Some(0x0A) => Text::Line(mem::take(&mut self.line)),
+                    Some(c) => {
+                        self.line.push(char::from(c as u8));
+                        continue;
+                    }
+                    None => Text::Value(v),
+                },
+                Io::NeedsInput => Text::NeedsInput,
+                Io::Halted => Text::Halted,
+            };
+
+            if !self.line.is_empty() && !matches!(text, Text::Line(_)) {
+                // The partial line came first
+                self.pending = Some(text);
+                return Ok(Text::Line(mem::take(&mut self.line)));
+            }
+
+            return Ok(text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn lines_values_and_commands() {
+        // Greets, echoes one line, reports a score and prints a
+        // prompt without a newline
+        let program = assemble(
+            "
+                  out  #72
+                  out  #105
+                  out  #10
+            echo: in   [c]
+                  out  [c]
+                  eq   [c], #10, [f]
+                  jf   [f], #echo
+                  out  #1000
+                  out  #62
+                  hlt
+            c:    data 0
+            f:    data 0
+            ",
+        )
+        .unwrap();
+        let mut ascii = Ascii::new(Computer::new(program));
+
+        assert_eq!(ascii.read(), Ok(Text::Line("Hi".into())));
+        assert_eq!(ascii.read(), Ok(Text::NeedsInput));
+
+        ascii.send("go");
+        assert_eq!(ascii.read(), Ok(Text::Line("go".into())));
+        assert_eq!(ascii.read(), Ok(Text::Value(1000)));
+        assert_eq!(ascii.read(), Ok(Text::Line(">".into())));
+        assert_eq!(ascii.read(), Ok(Text::Halted));
+    }
+
+    #[test]
+    fn partial_lines_come_before_values() {
+        let program: Program = vec![104, 65, 104, 1000, 104, 66, 104, 10, 99];
+        let mut ascii = Ascii::new(Computer::new(program));
+
+        assert_eq!(ascii.read(), Ok(Text::Line("A".into())));
+        assert_eq!(ascii.read(), Ok(Text::Value(1000)));
+        assert_eq!(ascii.read(), Ok(Text::Line("B".into())));
+        assert_eq!(ascii.read(), Ok(Text::Halted));
+    }
+}
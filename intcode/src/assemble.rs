@@ -0,0 +1,370 @@
+use crate::{Byte, Mode, Operation, Parameter, Program, ProgramCounter};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+#[derive(Debug, Copy, Clone)]
+enum Value<'a> {
+    Number(Byte),
+    Label(&'a str),
+}
+
+#[derive(Debug)]
+enum Item<'a> {
+    Instruction(Byte, Vec<(Mode, Value<'a>)>),
+    Data(Vec<Value<'a>>),
+}
+
+/// Every operation, so that mnemonics, opcodes and parameter counts
+/// come from `Operation` itself.
+fn operations() -> [Operation; 10] {
+    use Operation::*;
+    let p = Parameter::Immediate(0);
+
+    [
+        Add(p, p, p),
+        Multiply(p, p, p),
+        Input(p),
+        Output(p),
+        JumpIfTrue(p, p),
+        JumpIfFalse(p, p),
+        LessThan(p, p, p),
+        Equals(p, p, p),
+        AdjustRelativeBase(p),
+        Halt,
+    ]
+}
+
+/// Assembles the text format produced by `listing`. Each line holds
+/// any number of `label:` definitions followed by an instruction or a
+/// `data` directive; anything after a `;` is a comment. A numeric
+/// prefix such as `12:` asserts the address of the line.
+///
+/// Operands are written `[addr]`, `#imm` or `rb+off`, where `addr` and
+/// `imm` may also be labels.
+pub fn assemble(source: &str) -> Result<Program, AssemblyError> {
+    let mut labels = BTreeMap::new();
+    let mut items = Vec::new();
+    let mut address: ProgramCounter = 0;
+
+    for (line, text) in (1..).zip(source.lines()) {
+        let error = |message: String| AssemblyError { line, message };
+
+        let mut rest = text.split(';').next().unwrap_or("").trim();
+
+        while let Some(colon) = rest.find(':') {
+            let name = rest[..colon].trim();
+
+            if let Ok(expected) = name.parse::<ProgramCounter>() {
+                if expected != address {
+                    return Err(error(format!(
+                        "expected address {} but this is address {}",
+                        expected, address
+                    )));
+                }
+            } else if is_identifier(name) {
+                if labels.insert(name, address).is_some() {
+                    return Err(error(format!("label `{}` is already defined", name)));
+                }
+            } else {
+                return Err(error(format!("invalid label `{}`", name)));
+            }
+
+            rest = rest[colon + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, args) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+        let args: Vec<_> = if args.is_empty() {
+            vec![]
+        } else {
+            args.split(',').map(str::trim).collect()
+        };
+
+        let item = if mnemonic == "data" {
+            if args.is_empty() {
+                return Err(error("`data` needs at least one value".into()));
+            }
+            let values = args
+                .iter()
+                .map(|a| parse_value(a).map_err(&error))
+                .collect::<Result<Vec<_>, _>>()?;
+            Item::Data(values)
+        } else {
+            let op = operations()
+                .iter()
+                .copied()
+                .find(|op| op.mnemonic() == mnemonic)
+                .ok_or_else(|| error(format!("unknown mnemonic `{}`", mnemonic)))?;
+            let arity = op.parameters().len();
+
+            if args.len() != arity {
+                return Err(error(format!(
+                    "`{}` takes {} operands but {} were given",
+                    mnemonic,
+                    arity,
+                    args.len()
+                )));
+            }
+
+            let operands = args
+                .iter()
+                .map(|a| parse_operand(a).map_err(&error))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if op.destination().is_some() {
+                if let Some((Mode::Immediate, _)) = operands.last() {
+                    return Err(error(format!(
+                        "`{}` cannot write to an immediate operand",
+                        mnemonic
+                    )));
+                }
+            }
+
+            Item::Instruction(Byte::from(op.opcode()), operands)
+        };
+
+        address += match &item {
+            Item::Instruction(_, operands) => 1 + operands.len(),
+            Item::Data(values) => values.len(),
+        };
+        items.push((line, item));
+    }
+
+    let mut program = Program::with_capacity(address);
+
+    for (line, item) in items {
+        let resolve = |value| match value {
+            Value::Number(n) => Ok(n),
+            Value::Label(name) => {
+                labels
+                    .get(name)
+                    .map(|&a| a as Byte)
+                    .ok_or_else(|| AssemblyError {
+                        line,
+                        message: format!("undefined label `{}`", name),
+                    })
+            }
+        };
+
+        match item {
+            Item::Instruction(opcode, operands) => {
+                let mut raw_op = opcode;
+                let mut scale = 100;
+                for &(mode, _) in &operands {
+                    raw_op += mode as Byte * scale;
+                    scale *= 10;
+                }
+                program.push(raw_op);
+
+                for (_, value) in operands {
+                    program.push(resolve(value)?);
+                }
+            }
+            Item::Data(values) => {
+                for value in values {
+                    program.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(s: &str) -> Result<Value<'_>, String> {
+    if is_identifier(s) {
+        Ok(Value::Label(s))
+    } else {
+        s.parse()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid value `{}`", s))
+    }
+}
+
+fn parse_operand(s: &str) -> Result<(Mode, Value<'_>), String> {
+    if s.starts_with('[') && s.ends_with(']') {
+        Ok((Mode::Position, parse_value(s[1..s.len() - 1].trim())?))
+    } else if let Some(v) = s.strip_prefix('#') {
+        Ok((Mode::Immediate, parse_value(v.trim())?))
+    } else if let Some(offset) = s.strip_prefix("rb") {
+        let offset = offset.trim();
+        let offset = offset.strip_prefix('+').unwrap_or(offset).trim();
+        if offset.is_empty() {
+            return Ok((Mode::Relative, Value::Number(0)));
+        }
+        offset
+            .parse()
+            .map(|o| (Mode::Relative, Value::Number(o)))
+            .map_err(|_| format!("invalid relative offset in `{}`", s))
+    } else {
+        Err(format!(
+            "invalid operand `{}`; expected `[addr]`, `#imm` or `rb+off`",
+            s
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{execute, listing};
+
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    type Result<T, E = Error> = std::result::Result<T, E>;
+
+    #[test]
+    fn labels_and_data() -> Result<()> {
+        let program = assemble(
+            "
+                  in   [value]
+                  eq   [value], [eight], [value]
+                  out  [value]
+                  hlt
+            value: data -1
+            eight: data 8
+            ",
+        )?;
+        assert_eq!(program, [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+
+        assert_eq!(execute(&mut program.clone(), Some(8))?, [1]);
+        assert_eq!(execute(&mut program.clone(), Some(7))?, [0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_and_immediate_operands() -> Result<()> {
+        let program = assemble(
+            "
+                  arb  #base
+                  add  rb+1, #-1, rb-1 ; store one less than the data
+                  out  rb-1
+                  hlt
+                  data 0
+            base: data 0, 42
+            ",
+        )?;
+        assert_eq!(program, [109, 10, 21201, 1, -1, -1, 204, -1, 99, 0, 0, 42]);
+        assert_eq!(execute(&mut program.clone(), None)?, [41]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn jumps_use_forward_labels() -> Result<()> {
+        let program = assemble(
+            "
+                  in   [flag]
+                  jf   [flag], #zero
+                  out  #1
+                  hlt
+            zero: out  #0
+                  hlt
+            flag: data 0
+            ",
+        )?;
+
+        assert_eq!(execute(&mut program.clone(), Some(5))?, [1]);
+        assert_eq!(execute(&mut program.clone(), Some(0))?, [0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_listing() -> Result<()> {
+        let original = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99, 42, 301,
+        ];
+        let listing = listing(&original);
+        assert_eq!(assemble(&listing)?, original);
+
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_non_canonical_words() -> Result<()> {
+        // A fixed xorshift sequence, so that failures are repeatable
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n) as Byte
+        };
+
+        let opcodes = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99, 0, 42];
+        for _ in 0..1000 {
+            let len = 1 + next(20);
+            let original: Program = (0..len)
+                .map(|_| match next(4) {
+                    // Instructions with any mode digits, including ones
+                    // that are unused, invalid or past the fifth digit
+                    0 | 1 => {
+                        let opcode = opcodes[next(opcodes.len() as u64) as usize];
+                        let modes = next(4) * 100 + next(4) * 1_000 + next(4) * 10_000;
+                        let high = if next(4) == 0 { next(10) * 100_000 } else { 0 };
+                        let word = opcode + modes + high;
+                        if next(8) == 0 {
+                            -word
+                        } else {
+                            word
+                        }
+                    }
+                    2 => next(50) - 10,
+                    _ => next(1 << 40) - (1 << 39),
+                })
+                .collect();
+
+            let listing = listing(&original);
+            assert_eq!(assemble(&listing)?, original, "{}", listing);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        let error = |s| assemble(s).unwrap_err();
+
+        assert_eq!(error("hlt\nfrob #1").line, 2);
+        assert_eq!(error("hlt\n\nadd #1, #2").line, 3);
+        assert_eq!(error("in #1").line, 1);
+        assert_eq!(error("out [nowhere]").line, 1);
+        assert_eq!(error("a: hlt\na: hlt").line, 2);
+        assert_eq!(error("out 12").line, 1);
+        assert_eq!(error("hlt\n0: hlt").line, 2);
+        assert_eq!(error("data").line, 1);
+
+        assert_eq!(
+            error("hlt\nfrob #1").to_string(),
+            "line 2: unknown mnemonic `frob`"
+        );
+    }
+}
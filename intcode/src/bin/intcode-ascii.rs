@@ -0,0 +1,42 @@
+use intcode::{Ascii, Computer, Program, Text};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    process,
+};
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+fn main() -> Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-ascii <program>");
+            process::exit(2);
+        }
+    };
+    let program: Program = intcode::load_program(path)?;
+    let mut ascii = Ascii::new(Computer::new(program));
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    loop {
+        match ascii.read()? {
+            Text::Line(line) => writeln!(out, "{}", line)?,
+            Text::Value(v) => writeln!(out, "{}", v)?,
+            Text::NeedsInput => {
+                out.flush()?;
+
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line)? == 0 {
+                    return Ok(());
+                }
+                ascii.send(line.trim_end_matches(&['\r', '\n'][..]));
+            }
+            Text::Halted => return Ok(()),
+        }
+    }
+}
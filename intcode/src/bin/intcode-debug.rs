@@ -0,0 +1,340 @@
+use intcode::{disassemble, Access, Computer, Memory, Program, Status};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    process,
+};
+
+type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+const HELP: &str = "\
+break <addr>         stop before executing the instruction at <addr>
+watch <addr> [kind]  stop after an instruction accesses <addr>; <kind> is
+                     `write` (the default), `read` or `rw`
+delete <addr>        remove a breakpoint or watchpoint
+step [n]             execute one (or n) instructions
+continue [n]         run until a breakpoint, watchpoint, input or halt, or
+                     until <n> instructions have executed
+print <addr>[..len]  show memory
+set <addr> <value>   change memory
+rb                   show the relative base
+input <values>       queue input values, separated by commas or spaces
+list                 show the disassembly around the pc
+quit                 leave the debugger";
+
+/// How many instructions to show when listing, from the pc on.
+const LISTING_LINES: usize = 6;
+
+/// How many instructions before the pc to show when listing.
+const LISTING_CONTEXT: usize = 3;
+
+/// The widest instruction takes four words.
+const MAX_WIDTH: usize = 4;
+
+/// The most words that `print` will show at once.
+const MAX_PRINT: usize = 1000;
+
+struct Debugger {
+    computer: Computer,
+}
+
+enum Stop {
+    Continue,
+    Quit,
+}
+
+impl Debugger {
+    fn new(computer: Computer) -> Self {
+        Self { computer }
+    }
+
+    fn command(&mut self, line: &str, out: &mut impl Write) -> Result<Stop> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(c) => c,
+            None => return Ok(Stop::Continue),
+        };
+        let args: Vec<_> = words.collect();
+
+        match (command, &args[..]) {
+            ("break" | "b", [addr]) => self.computer.add_breakpoint(addr.parse()?),
+            ("watch" | "w", [addr]) => self.computer.add_watchpoint(addr.parse()?, Access::Write),
+            ("watch" | "w", [addr, kind]) => {
+                let access = match *kind {
+                    "read" => Access::Read,
+                    "write" => Access::Write,
+                    "rw" => Access::ReadWrite,
+                    _ => return Err(format!("Unknown watchpoint kind `{}`", kind).into()),
+                };
+                self.computer.add_watchpoint(addr.parse()?, access);
+            }
+            ("delete" | "d", [addr]) => {
+                let addr = addr.parse()?;
+                self.computer.remove_breakpoint(addr);
+                self.computer.remove_watchpoint(addr);
+            }
+            ("step" | "s", []) => self.step(1, out)?,
+            ("step" | "s", [n]) => self.step(n.parse()?, out)?,
+            ("continue" | "c", []) => self.run(None, out)?,
+            ("continue" | "c", [n]) => self.run(Some(n.parse()?), out)?,
+            ("print" | "p", [range]) => {
+                let (start, len) = match range.find("..") {
+                    Some(i) => (range[..i].parse()?, range[i + 2..].parse()?),
+                    None => (range.parse()?, 1),
+                };
+                let end = match usize::checked_add(start, len) {
+                    Some(end) if len <= MAX_PRINT => end,
+                    _ => return Err(format!("Can print at most {} values", MAX_PRINT).into()),
+                };
+                let values: Vec<_> = (start..end)
+                    .map(|a| self.computer.program.get(a).to_string())
+                    .collect();
+                writeln!(out, "{}: {}", start, values.join(", "))?;
+            }
+            ("set", [addr, value]) => {
+                let addr = addr.parse()?;
+                let value = value.parse()?;
+                self.computer.program.set(addr, value)?;
+            }
+            ("rb", []) => writeln!(out, "rb = {}", self.computer.relative_base())?,
+            ("input" | "i", values) if !values.is_empty() => {
+                for value in values.iter().flat_map(|v| v.split(',')) {
+                    if !value.is_empty() {
+                        self.computer.push_input(value.parse()?);
+                    }
+                }
+            }
+            ("list" | "l", []) => self.list(out)?,
+            ("help" | "h", []) => writeln!(out, "{}", HELP)?,
+            ("quit" | "q", []) => return Ok(Stop::Quit),
+            _ => writeln!(out, "Unknown command `{}`; try `help`", line.trim())?,
+        }
+
+        Ok(Stop::Continue)
+    }
+
+    fn step(&mut self, count: usize, out: &mut impl Write) -> Result<()> {
+        for _ in 0..count {
+            let step = self.computer.step()?;
+            if let Some(status) = step.status {
+                if Self::report(status, out)? {
+                    break;
+                }
+            }
+        }
+        self.list(out)
+    }
+
+    fn run(&mut self, limit: Option<u64>, out: &mut impl Write) -> Result<()> {
+        self.computer.set_instruction_limit(limit);
+        let result = self.run_until_paused(out);
+        self.computer.set_instruction_limit(None);
+        result?;
+        self.list(out)
+    }
+
+    fn run_until_paused(&mut self, out: &mut impl Write) -> Result<()> {
+        while !Self::report(self.computer.run()?, out)? {}
+        Ok(())
+    }
+
+    /// Describes why execution paused, returning true if it should
+    /// stay paused.
+    fn report(status: Status, out: &mut impl Write) -> Result<bool> {
+        match status {
+            Status::Output(v) => {
+                writeln!(out, "Output: {}", v)?;
+                return Ok(false);
+            }
+            Status::NeedsInput => writeln!(out, "Waiting for input")?,
+            Status::Halted => writeln!(out, "Halted")?,
+            Status::Breakpoint(pc) => writeln!(out, "Breakpoint at {}", pc)?,
+            Status::BudgetExhausted => writeln!(out, "Instruction limit reached")?,
+            Status::Watchpoint {
+                pc,
+                address,
+                access,
+                value,
+            } => {
+                let (verb, preposition) = match access {
+                    Access::Read => ("read", "from"),
+                    _ => ("wrote", "to"),
+                };
+                writeln!(
+                    out,
+                    "Watchpoint: {} {} {} {} {}",
+                    pc, verb, value, preposition, address
+                )?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn list(&self, out: &mut impl Write) -> Result<()> {
+        let pc = self.computer.pc();
+        let program = &self.computer.program;
+
+        // Nothing records where the previous instruction started, so
+        // use the earliest nearby start whose instructions lead to the pc
+        let earliest = pc.saturating_sub(LISTING_CONTEXT * MAX_WIDTH);
+        let before = (earliest..pc)
+            .find_map(|start| {
+                let lines: Vec<_> = disassemble(program, start)
+                    .take_while(|l| l.address < pc)
+                    .collect();
+                let last = lines.last()?;
+                if last.address + last.words.len() == pc {
+                    Some(lines)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let skip = before.len().saturating_sub(LISTING_CONTEXT);
+
+        let after = disassemble(program, pc).take(LISTING_LINES);
+        for line in before.into_iter().skip(skip).chain(after) {
+            let marker = if line.address == pc {
+                "=>"
+            } else if self.computer.breakpoints().any(|b| b == line.address) {
+                " *"
+            } else {
+                "  "
+            };
+            writeln!(out, "{} {}", marker, line)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-debug <program>");
+            process::exit(2);
+        }
+    };
+    let program: Program = intcode::load_program(path)?;
+    let mut debugger = Debugger::new(Computer::new(program));
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    debugger.list(&mut out)?;
+
+    loop {
+        write!(out, "(intcode) ")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        match debugger.command(&line, &mut out) {
+            Ok(Stop::Continue) => {}
+            Ok(Stop::Quit) => return Ok(()),
+            Err(e) => writeln!(out, "Error: {}", e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(debugger: &mut Debugger, commands: &[&str]) -> String {
+        let mut out = Vec::new();
+        for c in commands {
+            debugger.command(c, &mut out).expect("Command failed");
+        }
+        String::from_utf8(out).expect("Invalid UTF-8")
+    }
+
+    fn debugger(program: intcode::Program) -> Debugger {
+        Debugger::new(Computer::new(program))
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        // Adds the input to a counter until it reaches 10
+        let mut d = debugger(vec![
+            3, 100, 1, 100, 101, 101, 1007, 101, 10, 102, 1005, 102, 2, 99,
+        ]);
+
+        let out = run(&mut d, &["break 10", "input 4", "continue"]);
+        assert!(out.contains("Breakpoint at 10"));
+        assert_eq!(d.computer.program[101], 4);
+
+        let out = run(&mut d, &["delete 10", "watch 101", "continue"]);
+        assert!(out.contains("Watchpoint: 2 wrote 8 to 101"));
+
+        let out = run(&mut d, &["continue", "continue"]);
+        assert!(out.contains("Watchpoint: 2 wrote 12 to 101"));
+        assert!(out.contains("Halted"));
+    }
+
+    #[test]
+    fn read_watchpoints() {
+        let mut d = debugger(vec![4, 3, 99, 7]);
+
+        let out = run(&mut d, &["watch 3 read", "continue"]);
+        assert!(out.contains("Watchpoint: 0 read 7 from 3"));
+    }
+
+    #[test]
+    fn memory_commands() {
+        let mut d = debugger(vec![4, 3, 99, 7]);
+
+        let out = run(&mut d, &["print 0..4", "set 3 42", "print 3", "rb"]);
+        assert_eq!(out, "0: 4, 3, 99, 7\n3: 42\nrb = 0\n");
+
+        let out = run(&mut d, &["step"]);
+        assert!(out.starts_with("Output: 42\n       0: out [3]"));
+        assert!(out.contains("\n=>     2: hlt"));
+
+        let mut out = Vec::new();
+        assert!(d.command("print 5..1001", &mut out).is_err());
+        assert!(d
+            .command("print 18446744073709551615..2", &mut out)
+            .is_err());
+    }
+
+    #[test]
+    fn listing_shows_what_came_before() {
+        let mut d = debugger(vec![104, 5, 104, 6, 104, 7, 104, 8, 104, 9, 99]);
+
+        let out = run(&mut d, &["break 8", "continue"]);
+        let listing: Vec<_> = out
+            .lines()
+            .skip_while(|l| !l.starts_with("Break"))
+            .collect();
+        assert_eq!(listing.len(), 1 + LISTING_CONTEXT + 2);
+        assert!(listing[1].starts_with("       2: out #6"));
+        assert!(listing[4].starts_with("=>     8: out #9"));
+    }
+
+    #[test]
+    fn continue_with_a_limit() {
+        let mut d = debugger(vec![1105, 1, 0]);
+
+        let out = run(&mut d, &["continue 5"]);
+        assert!(out.starts_with("Instruction limit reached\n"));
+        assert_eq!(d.computer.instructions_retired(), 5);
+    }
+
+    #[test]
+    fn waits_for_input() {
+        let mut d = debugger(vec![3, 5, 4, 5, 99, 0]);
+
+        let out = run(&mut d, &["continue"]);
+        assert!(out.contains("Waiting for input"));
+
+        let out = run(&mut d, &["input 9", "continue"]);
+        assert!(out.contains("Output: 9"));
+        assert!(out.contains("Halted"));
+    }
+}
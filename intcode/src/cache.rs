@@ -0,0 +1,265 @@
+use crate::{Error, Memory, Operation, ProgramCounter, Word};
+use std::convert::TryInto;
+
+/// Addresses beyond this are decoded every time, so that code far out
+/// in sparse memory doesn't make the table of pages huge.
+const MAX_CACHED_ADDRESS: ProgramCounter = 1 << 20;
+
+/// Entries are allocated a page at a time, and only for pages that
+/// hold decoded code.
+const PAGE_BITS: u32 = 8;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+/// The widest instruction takes four words.
+const MAX_WIDTH: usize = 4;
+
+#[derive(Debug, Clone)]
+struct Entry<W> {
+    operation: Operation<W>,
+    /// The words the operation was decoded from.
+    words: Vec<W>,
+    /// The epoch in which `words` were last compared with memory.
+    checked: u64,
+}
+
+type Page<W> = Box<[Option<Entry<W>>; PAGE_SIZE]>;
+
+/// Decoded instructions by address, so that a loop is only decoded
+/// once. Writes made by instructions invalidate what they overwrite.
+///
+/// `Computer::program` can also be changed directly between calls to
+/// `run`, so each call starts a new epoch, and an entry is compared
+/// with memory the first time it is used in an epoch.
+#[derive(Debug, Clone)]
+pub(crate) struct DecodeCache<W> {
+    pages: Vec<Option<Page<W>>>,
+    /// One bit per address, set once a cached instruction has covered
+    /// it, so that most writes to data are ignored after one lookup.
+    code: Vec<u64>,
+    /// The last instruction decoded beyond `MAX_CACHED_ADDRESS`, or
+    /// anywhere when disabled.
+    uncached: Option<Entry<W>>,
+    epoch: u64,
+    disabled: bool,
+}
+
+impl<W> Default for DecodeCache<W> {
+    fn default() -> Self {
+        Self {
+            pages: Vec::new(),
+            code: Vec::new(),
+            uncached: None,
+            epoch: 0,
+            disabled: false,
+        }
+    }
+}
+
+impl<W> DecodeCache<W>
+where
+    W: Word,
+{
+    /// Forgets everything, and decodes every instruction afresh from
+    /// now on when `disabled`.
+    pub(crate) fn reset(&mut self, disabled: bool) {
+        *self = Self {
+            disabled,
+            ..Self::default()
+        };
+    }
+
+    /// Memory may have changed without `invalidate` being called.
+    pub(crate) fn start_epoch(&mut self) {
+        self.epoch += 1;
+    }
+
+    #[inline]
+    fn entry(&self, pc: ProgramCounter) -> Option<&Entry<W>> {
+        let page = self.pages.get(pc >> PAGE_BITS)?.as_ref()?;
+        page[pc % PAGE_SIZE].as_ref()
+    }
+
+    #[inline]
+    fn entry_mut(&mut self, pc: ProgramCounter) -> Option<&mut Option<Entry<W>>> {
+        let page = self.pages.get_mut(pc >> PAGE_BITS)?.as_mut()?;
+        Some(&mut page[pc % PAGE_SIZE])
+    }
+
+    #[inline]
+    pub(crate) fn decode<M>(
+        &mut self,
+        program: &M,
+        pc: ProgramCounter,
+    ) -> Result<&Operation<W>, Error<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let hit = self.entry(pc).is_some_and(|e| e.checked == self.epoch);
+        if hit {
+            return Ok(&self.entry(pc).expect("Just checked").operation);
+        }
+        self.decode_miss(program, pc)
+    }
+
+    /// The instruction at `pc`, if it has already been decoded and
+    /// checked in this epoch.
+    #[inline]
+    pub(crate) fn cached(&self, pc: ProgramCounter) -> Option<&Operation<W>> {
+        self.entry(pc)
+            .filter(|e| e.checked == self.epoch)
+            .map(|e| &e.operation)
+    }
+
+    #[inline(never)]
+    fn decode_miss<M>(&mut self, program: &M, pc: ProgramCounter) -> Result<&Operation<W>, Error<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let epoch = self.epoch;
+        if self.disabled {
+            let entry = self.uncached.insert(Entry {
+                operation: Operation::decode(program, pc)?,
+                words: Vec::new(),
+                checked: epoch,
+            });
+            return Ok(&entry.operation);
+        }
+
+        let fresh = match self.entry_mut(pc) {
+            Some(Some(entry)) => {
+                let unchanged = (pc..).zip(&entry.words).all(|(a, w)| program.get(a) == *w);
+                entry.checked = epoch;
+                unchanged
+            }
+            _ => false,
+        };
+
+        if !fresh {
+            let operation = Operation::decode(program, pc)?;
+            let entry = Entry {
+                words: (pc..pc + operation.width())
+                    .map(|a| program.get(a))
+                    .collect(),
+                operation,
+                checked: epoch,
+            };
+
+            if pc >= MAX_CACHED_ADDRESS {
+                let entry = self.uncached.insert(entry);
+                return Ok(&entry.operation);
+            }
+
+            let end = pc + entry.words.len();
+            if self.code.len() <= end / 64 {
+                self.code.resize(end / 64 + 1, 0);
+            }
+            for a in pc..end {
+                self.code[a / 64] |= 1 << (a % 64);
+            }
+
+            let index = pc >> PAGE_BITS;
+            if self.pages.len() <= index {
+                self.pages.resize_with(index + 1, || None);
+            }
+            let page = self.pages[index].get_or_insert_with(|| {
+                let slots: Box<[_]> = (0..PAGE_SIZE).map(|_| None).collect();
+                slots.try_into().expect("Page has the wrong size")
+            });
+            page[pc % PAGE_SIZE] = Some(entry);
+        }
+
+        Ok(&self.entry(pc).expect("Just decoded").operation)
+    }
+
+    /// Forgets every instruction that includes `address`.
+    #[inline]
+    pub(crate) fn invalidate(&mut self, address: ProgramCounter) {
+        let is_code = self
+            .code
+            .get(address / 64)
+            .is_some_and(|bits| bits & (1 << (address % 64)) != 0);
+        if !is_code {
+            return;
+        }
+
+        for pc in address.saturating_sub(MAX_WIDTH - 1)..=address {
+            if let Some(slot) = self.entry_mut(pc) {
+                if slot.as_ref().is_some_and(|e| address < pc + e.words.len()) {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, Parameter, Program, SparseMemory};
+
+    #[test]
+    fn writes_into_code_are_seen() {
+        let mut cache = DecodeCache::default();
+        let mut program: Program = vec![1001, 0, 5, 0, 99];
+
+        let add = Operation::Add(
+            Parameter::Position(0),
+            Parameter::Immediate(5),
+            Parameter::Position(0),
+        );
+        assert_eq!(cache.decode(&program, 0), Ok(&add));
+
+        // Invalidated entries are decoded again
+        program[2] = 6;
+        cache.invalidate(2);
+        assert!(cache.entry(0).is_none());
+        assert_eq!(
+            cache.decode(&program, 0),
+            Ok(&Operation::Add(
+                Parameter::Position(0),
+                Parameter::Immediate(6),
+                Parameter::Position(0),
+            ))
+        );
+
+        // Even without invalidating, a stale entry isn't used in a
+        // later epoch
+        program[0] = 1101;
+        cache.start_epoch();
+        assert_eq!(
+            cache.decode(&program, 0),
+            Ok(&Operation::Add(
+                Parameter::Immediate(0),
+                Parameter::Immediate(6),
+                Parameter::Position(0),
+            ))
+        );
+    }
+
+    #[test]
+    fn self_modifying_loop() {
+        // Rewrites its own `out #0` to output 0, 1, 2 then halts once
+        // the counter reaches 3
+        let mut computer: Computer = "104,0,1001,1,1,1,1008,1,3,14,1006,14,0,99,0"
+            .parse()
+            .unwrap();
+        let mut output = Vec::new();
+        computer.execute(None, &mut output).unwrap();
+        assert_eq!(output, [0, 1, 2]);
+    }
+
+    #[test]
+    fn distant_code_only_allocates_its_own_page() {
+        let mut memory: SparseMemory<i64> = SparseMemory::default();
+        for (a, &v) in [1105, 1, 1_000_000].iter().enumerate() {
+            memory.set(a, v).unwrap();
+        }
+        memory.set(1_000_000, 99).unwrap();
+        let mut computer = Computer::new(memory);
+        computer.execute(None, &mut Vec::new()).unwrap();
+
+        let pages = &computer.decoded.pages;
+        assert_eq!(pages.iter().flatten().count(), 2);
+        assert!(pages.len() <= MAX_CACHED_ADDRESS / PAGE_SIZE);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{Byte, Memory, Operation, Parameter, ProgramCounter, Word};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How control passes from one block to another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// Straight on to the next instruction, including when a
+    /// conditional jump isn't taken.
+    Next,
+    Jump,
+    /// A jump to a subroutine, made after storing the return address
+    /// in a relative-base slot.
+    Call,
+    /// From a call to its return address, where the subroutine will
+    /// come back to.
+    AfterCall,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: ProgramCounter,
+    pub to: ProgramCounter,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only entered at the top and only
+/// left at the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<W = Byte> {
+    pub start: ProgramCounter,
+    pub instructions: Vec<(ProgramCounter, Operation<W>)>,
+}
+
+impl<W> Block<W>
+where
+    W: Word,
+{
+    /// The address just past the last instruction.
+    pub fn end(&self) -> ProgramCounter {
+        self.instructions
+            .last()
+            .map_or(self.start, |(pc, op)| pc + op.width())
+    }
+}
+
+/// The control-flow graph of the code reachable from address 0,
+/// found without running the program. Blocks and edges are keyed by
+/// the address of the block's first instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg<W = Byte> {
+    pub blocks: BTreeMap<ProgramCounter, Block<W>>,
+    pub edges: BTreeSet<Edge>,
+    /// The first instruction of each subroutine.
+    pub subroutines: BTreeSet<ProgramCounter>,
+    /// Jumps whose target is read from memory, such as returns from a
+    /// subroutine. Their targets aren't followed.
+    pub indirect_jumps: BTreeSet<ProgramCounter>,
+    /// Instructions that write into code, keyed by the instruction,
+    /// with the address written.
+    pub code_writes: BTreeMap<ProgramCounter, ProgramCounter>,
+    /// Reachable addresses that don't decode as an instruction.
+    pub undecodable: BTreeSet<ProgramCounter>,
+}
+
+struct Flow {
+    targets: Vec<(ProgramCounter, EdgeKind)>,
+    /// Whether control can reach the next instruction.
+    falls_through: bool,
+    /// Whether this instruction must end its block.
+    ends_block: bool,
+}
+
+/// Works out where control can go after `op`, given the instruction
+/// that ran just before it, if any.
+fn flow<W: Word>(
+    pc: ProgramCounter,
+    op: &Operation<W>,
+    previous: Option<&Operation<W>>,
+    cfg: &mut Cfg<W>,
+) -> Flow {
+    use Operation::*;
+
+    let (condition, target, jump_if) = match op {
+        JumpIfTrue(c, t) => (c, t, true),
+        JumpIfFalse(c, t) => (c, t, false),
+        Halt => {
+            return Flow {
+                targets: vec![],
+                falls_through: false,
+                ends_block: true,
+            }
+        }
+        _ => {
+            return Flow {
+                targets: vec![],
+                falls_through: true,
+                ends_block: false,
+            }
+        }
+    };
+
+    let next = pc + op.width();
+    let (always, never) = match condition {
+        Parameter::Immediate(c) => {
+            let taken = (*c != W::zero()) == jump_if;
+            (taken, !taken)
+        }
+        _ => (false, false),
+    };
+
+    let mut targets = Vec::new();
+    if !never {
+        match target {
+            Parameter::Immediate(t) => {
+                if let Some(t) = t.to_usize() {
+                    if always && stores_return_address(previous, next) {
+                        cfg.subroutines.insert(t);
+                        targets.push((t, EdgeKind::Call));
+                        targets.push((next, EdgeKind::AfterCall));
+                    } else {
+                        targets.push((t, EdgeKind::Jump));
+                    }
+                }
+            }
+            _ => {
+                cfg.indirect_jumps.insert(pc);
+            }
+        }
+    }
+
+    Flow {
+        targets,
+        falls_through: !always,
+        ends_block: true,
+    }
+}
+
+/// Whether `op` puts the constant `address` in a relative-base slot.
+fn stores_return_address<W: Word>(op: Option<&Operation<W>>, address: ProgramCounter) -> bool {
+    let value = match op {
+        Some(Operation::Add(
+            Parameter::Immediate(l),
+            Parameter::Immediate(r),
+            Parameter::Relative(_),
+        )) => l.checked_add(r),
+        Some(Operation::Multiply(
+            Parameter::Immediate(l),
+            Parameter::Immediate(r),
+            Parameter::Relative(_),
+        )) => l.checked_mul(r),
+        _ => None,
+    };
+    value.and_then(|v| v.to_usize()) == Some(address)
+}
+
+/// Builds the control-flow graph of `program`.
+pub fn control_flow_graph<M: Memory>(program: &M) -> Cfg<M::Word> {
+    let mut cfg = Cfg {
+        blocks: BTreeMap::new(),
+        edges: BTreeSet::new(),
+        subroutines: BTreeSet::new(),
+        indirect_jumps: BTreeSet::new(),
+        code_writes: BTreeMap::new(),
+        undecodable: BTreeSet::new(),
+    };
+
+    // Each reachable instruction, with where control goes after it
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut work = vec![0];
+
+    while let Some(start) = work.pop() {
+        leaders.insert(start);
+        let mut pc = start;
+        let mut previous = None;
+
+        while pc < program.len() && !instructions.contains_key(&pc) {
+            let op = match Operation::decode(program, pc) {
+                Ok(op) => op,
+                Err(_) => {
+                    cfg.undecodable.insert(pc);
+                    break;
+                }
+            };
+
+            let flow = flow(pc, &op, previous.as_ref(), &mut cfg);
+            let next = pc + op.width();
+
+            for &(target, _) in &flow.targets {
+                work.push(target);
+            }
+            if flow.ends_block && flow.falls_through {
+                leaders.insert(next);
+            }
+
+            let falls_through = flow.falls_through;
+            instructions.insert(pc, (op.clone(), flow));
+            if !falls_through {
+                break;
+            }
+            previous = Some(op);
+            pc = next;
+        }
+    }
+
+    // Split the instructions into blocks
+    let mut current: Option<Block<M::Word>> = None;
+    for (&pc, (op, flow)) in &instructions {
+        let continues = current
+            .as_ref()
+            .is_some_and(|b| b.end() == pc && !leaders.contains(&pc));
+
+        if !continues {
+            if let Some(block) = current.take() {
+                if block.end() == pc {
+                    cfg.edges.insert(Edge {
+                        from: block.start,
+                        to: pc,
+                        kind: EdgeKind::Next,
+                    });
+                }
+                cfg.blocks.insert(block.start, block);
+            }
+        }
+
+        let block = current.get_or_insert_with(|| Block {
+            start: pc,
+            instructions: Vec::new(),
+        });
+        block.instructions.push((pc, op.clone()));
+
+        if flow.ends_block {
+            for &(to, kind) in &flow.targets {
+                cfg.edges.insert(Edge {
+                    from: block.start,
+                    to,
+                    kind,
+                });
+            }
+            if flow.falls_through {
+                cfg.edges.insert(Edge {
+                    from: block.start,
+                    to: pc + op.width(),
+                    kind: EdgeKind::Next,
+                });
+            }
+            let block = current.take().expect("A block is being built");
+            cfg.blocks.insert(block.start, block);
+        }
+    }
+    if let Some(block) = current {
+        cfg.blocks.insert(block.start, block);
+    }
+
+    // Look for writes that land inside an instruction
+    for (&pc, (op, _)) in &instructions {
+        if let Some(&Parameter::Position(address)) = op.destination() {
+            let in_code = instructions
+                .range(..=address)
+                .next_back()
+                .is_some_and(|(&start, (op, _))| address < start + op.width());
+            if in_code {
+                cfg.code_writes.insert(pc, address);
+            }
+        }
+    }
+
+    cfg
+}
+
+impl<W> Cfg<W>
+where
+    W: Word,
+{
+    /// The graph in Graphviz DOT format. Subroutines are drawn with a
+    /// double border, and indirect jumps and writes into code are
+    /// marked in red.
+    pub fn dot(&self) -> String {
+        let mut dot = String::new();
+
+        let mut line = |s: String| {
+            dot.push_str(&s);
+            dot.push('\n');
+        };
+
+        line("digraph cfg {".into());
+        line("    node [shape=box, fontname=monospace];".into());
+
+        for (start, block) in &self.blocks {
+            let mut label = String::new();
+            let mut marked = false;
+
+            for (pc, op) in &block.instructions {
+                label.push_str(&format!("{:>5}: {}", pc, op));
+                if self.indirect_jumps.contains(pc) {
+                    label.push_str(" ; indirect");
+                    marked = true;
+                }
+                if let Some(address) = self.code_writes.get(pc) {
+                    label.push_str(&format!(" ; writes code at {}", address));
+                    marked = true;
+                }
+                label.push_str("\\l");
+            }
+
+            let mut attributes = format!("label=\"{}\"", label);
+            if self.subroutines.contains(start) {
+                attributes.push_str(", peripheries=2");
+            }
+            if marked {
+                attributes.push_str(", color=red");
+            }
+            line(format!("    b{} [{}];", start, attributes));
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\", style=bold]",
+                EdgeKind::AfterCall => " [style=dashed]",
+            };
+            line(format!("    b{} -> b{}{};", edge.from, edge.to, style));
+        }
+
+        line("}".into());
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn finds_subroutines_and_code_writes() {
+        let program = assemble(
+            "
+             0:       arb  #100
+             2:       add  #9, #0, rb+0     ; call double
+             6:       jt   #1, #double
+             9:       out  [value]
+            11:       add  #4, #0, [patch]  ; changes the next instruction
+            15: patch: out  #0
+            17:       hlt
+            18: double: mul [value], #2, [value]
+            22:       jf   [value], #17
+            25:       jt   #1, rb+0          ; return
+            28: value: data 21
+            ",
+        )
+        .unwrap();
+        let cfg = control_flow_graph(&program);
+
+        let starts: Vec<_> = cfg.blocks.keys().copied().collect();
+        assert_eq!(starts, [0, 9, 17, 18, 25]);
+        assert_eq!(cfg.blocks[&9].end(), 17);
+
+        let edges: Vec<_> = cfg.edges.iter().map(|e| (e.from, e.to, e.kind)).collect();
+        assert_eq!(
+            edges,
+            [
+                (0, 9, EdgeKind::AfterCall),
+                (0, 18, EdgeKind::Call),
+                (9, 17, EdgeKind::Next),
+                (18, 17, EdgeKind::Jump),
+                (18, 25, EdgeKind::Next),
+            ]
+        );
+
+        assert_eq!(cfg.subroutines.iter().collect::<Vec<_>>(), [&18]);
+        assert_eq!(cfg.indirect_jumps.iter().collect::<Vec<_>>(), [&25]);
+        assert_eq!(cfg.code_writes.iter().collect::<Vec<_>>(), [(&11, &15)]);
+        assert!(cfg.undecodable.is_empty());
+
+        let dot = cfg.dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    b18 [label=\"   18: mul [28], #2, [28]\\l"));
+        assert!(dot.contains("jt #1, rb+0 ; indirect\\l\", color=red];"));
+        assert!(dot.contains("    b0 -> b18 [label=\"call\", style=bold];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
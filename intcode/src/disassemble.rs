@@ -0,0 +1,161 @@
+use crate::{Byte, Memory, Operation, Parameter, ProgramCounter, Word};
+use itertools::Itertools;
+use std::{fmt, iter};
+
+/// One line of a listing: a decoded instruction, or a single word that
+/// is shown as data. A word is data when it doesn't decode, or when
+/// `assemble` wouldn't produce it, such as an instruction with unused
+/// mode digits or an immediate destination, so that a listing always
+/// assembles back to the same words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line<W = Byte> {
+    pub address: ProgramCounter,
+    pub words: Vec<W>,
+    pub operation: Option<Operation<W>>,
+}
+
+impl<W> fmt::Display for Line<W>
+where
+    W: Word,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.operation {
+            Some(op) => write!(
+                f,
+                "{:>5}: {:<28} ; {}",
+                self.address,
+                op.to_string(),
+                self.words.iter().join(", ")
+            ),
+            None => write!(f, "{:>5}: data {}", self.address, self.words[0]),
+        }
+    }
+}
+
+/// Decodes the program linearly from `start`, using the same decoding
+/// as the interpreter. Gaps between the regions of sparse memory are
+/// skipped.
+pub fn disassemble<M: Memory>(
+    program: &M,
+    start: ProgramCounter,
+) -> impl Iterator<Item = Line<M::Word>> + '_ {
+    let mut address = start;
+    let mut regions = program
+        .regions()
+        .into_iter()
+        .map(|(start, values)| (start, start + values.len()))
+        .peekable();
+
+    iter::from_fn(move || {
+        while let Some(&(start, end)) = regions.peek() {
+            if address < end {
+                address = address.max(start);
+                break;
+            }
+            regions.next();
+        }
+        if regions.peek().is_none() || address >= program.len() {
+            return None;
+        }
+
+        let decoded = Operation::decode(program, address).ok().filter(|op| {
+            let canonical = program.get(address) == M::Word::from_i64(op.instruction());
+            let writes_immediate = matches!(op.destination(), Some(Parameter::Immediate(_)));
+            canonical && !writes_immediate
+        });
+
+        let line = match decoded {
+            Some(op) => Line {
+                address,
+                words: (address..address + op.width())
+                    .map(|a| program.get(a))
+                    .collect(),
+                operation: Some(op),
+            },
+            None => Line {
+                address,
+                words: vec![program.get(address)],
+                operation: None,
+            },
+        };
+
+        address += line.words.len();
+        Some(line)
+    })
+}
+
+pub fn listing(program: &impl Memory) -> String {
+    disassemble(program, 0)
+        .map(|l| format!("{}\n", l))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Program, SparseMemory};
+
+    #[test]
+    fn all_modes() {
+        let program: Program = vec![1002, 4, 3, 4, 22201, 1, -2, 3, 99];
+        let lines: Vec<_> = disassemble(&program, 0).map(|l| l.to_string()).collect();
+
+        assert_eq!(
+            lines,
+            [
+                "    0: mul [4], #3, [4]             ; 1002, 4, 3, 4",
+                "    4: add rb+1, rb-2, rb+3         ; 22201, 1, -2, 3",
+                "    8: hlt                          ; 99",
+            ]
+        );
+    }
+
+    #[test]
+    fn unrecognised_words_are_data() {
+        let program: Program = vec![104, 7, 42, 301, 1, -5, 4];
+        let lines: Vec<_> = disassemble(&program, 0).collect();
+
+        let addresses: Vec<_> = lines.iter().map(|l| l.address).collect();
+        assert_eq!(addresses, [0, 2, 3, 4, 5, 6]);
+        assert_eq!(lines[1].to_string(), "    2: data 42");
+        assert_eq!(lines[2].to_string(), "    3: data 301");
+        assert_eq!(lines[4].to_string(), "    5: data -5");
+        assert_eq!(lines[5].to_string(), "    6: data 4");
+    }
+
+    #[test]
+    fn words_assemble_wouldnt_produce_are_data() {
+        let program: Program = vec![1104, 42, 100099, 11101, 1, 2, 3, 99];
+        let lines: Vec<_> = disassemble(&program, 0).map(|l| l.to_string()).collect();
+
+        assert_eq!(lines[0], "    0: data 1104");
+        assert_eq!(lines[2], "    2: data 100099");
+        // Writes to an immediate, which `assemble` rejects
+        assert_eq!(lines[3], "    3: data 11101");
+        assert_eq!(
+            lines[4],
+            "    4: add [2], [3], [99]           ; 1, 2, 3, 99"
+        );
+    }
+
+    #[test]
+    fn gaps_in_sparse_memory_are_skipped() {
+        let mut program: SparseMemory = SparseMemory::from(vec![104, 7, 99]);
+        program.set(1_000_000_000_000, 42).unwrap();
+        let lines: Vec<_> = disassemble(&program, 0).collect();
+
+        // The rest of each page is still listed
+        let addresses: Vec<_> = lines.iter().map(|l| l.address).collect();
+        assert_eq!(addresses.len(), 2 + 1021 + 1);
+        assert_eq!(addresses[..4], [0, 2, 3, 4]);
+        assert_eq!(lines.last().unwrap().to_string(), "1000000000000: data 42");
+    }
+
+    #[test]
+    fn starting_part_way() {
+        let program: Program = vec![1, 0, 0, 0, 99];
+        let lines: Vec<_> = disassemble(&program, 4).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].operation, Some(Operation::Halt));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{Byte, Memory, ProgramCounter, Word};
+use std::fmt;
+
+/// A failure while decoding or executing an instruction. Each variant
+/// records the program counter and the raw instruction found there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error<W = Byte> {
+    UnknownOpcode {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    UnknownMode {
+        pc: ProgramCounter,
+        instruction: W,
+        mode: i64,
+    },
+    NegativeAddress {
+        pc: ProgramCounter,
+        instruction: W,
+        address: W,
+    },
+    AddressOutOfRange {
+        pc: ProgramCounter,
+        instruction: W,
+        address: W,
+    },
+    WriteToImmediate {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    InputExhausted {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    /// The output stream stopped accepting values. `pc` is that of
+    /// the output instruction.
+    OutputClosed {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    /// The result of an `Add` or `Multiply` doesn't fit in the word.
+    Overflow {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    /// The instruction limit or deadline was reached before the
+    /// program halted.
+    BudgetExhausted {
+        pc: ProgramCounter,
+        instruction: W,
+    },
+    /// The program counter, or the parameters of the instruction at it,
+    /// run past the end of memory. There is no instruction when the
+    /// program counter itself is past the end.
+    PcOutOfBounds {
+        pc: ProgramCounter,
+        instruction: Option<W>,
+    },
+}
+
+impl<W> Error<W> {
+    pub fn pc(&self) -> ProgramCounter {
+        use Error::*;
+
+        match *self {
+            UnknownOpcode { pc, .. }
+            | UnknownMode { pc, .. }
+            | NegativeAddress { pc, .. }
+            | AddressOutOfRange { pc, .. }
+            | WriteToImmediate { pc, .. }
+            | InputExhausted { pc, .. }
+            | OutputClosed { pc, .. }
+            | Overflow { pc, .. }
+            | BudgetExhausted { pc, .. }
+            | PcOutOfBounds { pc, .. } => pc,
+        }
+    }
+
+    pub fn instruction(&self) -> Option<&W> {
+        use Error::*;
+
+        match self {
+            UnknownOpcode { instruction, .. }
+            | UnknownMode { instruction, .. }
+            | NegativeAddress { instruction, .. }
+            | AddressOutOfRange { instruction, .. }
+            | WriteToImmediate { instruction, .. }
+            | InputExhausted { instruction, .. }
+            | OutputClosed { instruction, .. }
+            | Overflow { instruction, .. }
+            | BudgetExhausted { instruction, .. } => Some(instruction),
+            PcOutOfBounds { instruction, .. } => instruction.as_ref(),
+        }
+    }
+}
+
+impl<W> fmt::Display for Error<W>
+where
+    W: Word,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match self {
+            UnknownOpcode { instruction, .. } => write!(
+                f,
+                "Unknown opcode {}",
+                instruction.instruction_digits() % 100
+            )?,
+            UnknownMode { mode, .. } => write!(f, "Unknown mode {}", mode)?,
+            NegativeAddress { address, .. } => write!(f, "Negative address {}", address)?,
+            AddressOutOfRange { address, .. } => write!(f, "Address {} is out of range", address)?,
+            WriteToImmediate { .. } => write!(f, "Must not write to immediate parameter")?,
+            InputExhausted { .. } => write!(f, "No more input is available")?,
+            OutputClosed { .. } => write!(f, "Output is closed")?,
+            Overflow { .. } => write!(f, "Arithmetic overflow")?,
+            BudgetExhausted { .. } => write!(f, "Instruction budget exhausted")?,
+            PcOutOfBounds { .. } => write!(f, "Instruction runs past the end of memory")?,
+        }
+
+        write!(f, " at pc {}", self.pc())?;
+        if let Some(instruction) = self.instruction() {
+            write!(f, " (instruction {})", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W> std::error::Error for Error<W> where W: Word {}
+
+/// An `Error` that doesn't yet know where it happened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Fault<W> {
+    UnknownOpcode,
+    UnknownMode(i64),
+    InvalidAddress(W),
+    WriteToImmediate,
+    InputExhausted,
+    OutputClosed,
+    Overflow,
+    BudgetExhausted,
+    OutOfBounds,
+}
+
+impl<W> Fault<W>
+where
+    W: Word,
+{
+    pub(crate) fn at<M>(self, pc: ProgramCounter, program: &M) -> Error<W>
+    where
+        M: Memory<Word = W>,
+    {
+        if pc >= program.len() {
+            return Error::PcOutOfBounds {
+                pc,
+                instruction: None,
+            };
+        }
+        let instruction = program.get(pc);
+
+        match self {
+            Fault::UnknownOpcode => Error::UnknownOpcode { pc, instruction },
+            Fault::UnknownMode(mode) => Error::UnknownMode {
+                pc,
+                instruction,
+                mode,
+            },
+            Fault::InvalidAddress(address) if address < W::zero() => Error::NegativeAddress {
+                pc,
+                instruction,
+                address,
+            },
+            Fault::InvalidAddress(address) => Error::AddressOutOfRange {
+                pc,
+                instruction,
+                address,
+            },
+            Fault::WriteToImmediate => Error::WriteToImmediate { pc, instruction },
+            Fault::InputExhausted => Error::InputExhausted { pc, instruction },
+            Fault::OutputClosed => Error::OutputClosed { pc, instruction },
+            Fault::Overflow => Error::Overflow { pc, instruction },
+            Fault::BudgetExhausted => Error::BudgetExhausted { pc, instruction },
+            Fault::OutOfBounds => Error::PcOutOfBounds {
+                pc,
+                instruction: Some(instruction),
+            },
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{Byte, Operation, ProgramCounter, Snapshot};
+use std::{collections::VecDeque, mem};
+
+/// What one executed instruction changed, with enough detail to undo it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<W = Byte> {
+    pub pc: ProgramCounter,
+    pub relative_base: ProgramCounter,
+    pub operation: Operation<W>,
+    /// The address written, the value it held before and the value
+    /// written.
+    pub write: Option<(ProgramCounter, W, W)>,
+    /// The input value consumed by an `Input` instruction.
+    pub input: Option<W>,
+    /// The length of memory beforehand, which the write may have grown.
+    pub memory_len: usize,
+    pub next_pc: ProgramCounter,
+    pub next_relative_base: ProgramCounter,
+}
+
+/// The state of the machine at some point, and the input it has
+/// consumed since, which is enough to replay forward from there.
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint<W> {
+    /// How many instructions had executed when it was taken.
+    executed: u64,
+    snapshot: Snapshot<W>,
+    input: Vec<W>,
+}
+
+/// The past of a `Computer`: a record of each of the last `limit`
+/// instructions it executed, and a checkpoint of the whole machine
+/// every `limit` instructions. Older records are forgotten, which
+/// bounds memory use; going back past them means restoring the
+/// nearest checkpoint and replaying forward.
+#[derive(Debug, Clone)]
+pub struct History<W = Byte> {
+    limit: usize,
+    records: VecDeque<Record<W>>,
+    checkpoints: Vec<Checkpoint<W>>,
+    /// Instructions executed since recording started, less those
+    /// stepped back over.
+    executed: u64,
+}
+
+impl<W> History<W> {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            records: VecDeque::new(),
+            checkpoints: Vec::new(),
+            executed: 0,
+        }
+    }
+
+    /// Whether a checkpoint is due before the next instruction.
+    pub(crate) fn wants_checkpoint(&self) -> bool {
+        let due = self.executed % self.limit as u64 == 0;
+        due && self.checkpoints.last().map(|c| c.executed) != Some(self.executed)
+    }
+
+    pub(crate) fn checkpoint(&mut self, snapshot: Snapshot<W>) {
+        self.checkpoints.push(Checkpoint {
+            executed: self.executed,
+            snapshot,
+            input: Vec::new(),
+        });
+    }
+
+    pub(crate) fn push(&mut self, record: Record<W>)
+    where
+        W: Clone,
+    {
+        if let (Some(input), Some(checkpoint)) = (&record.input, self.checkpoints.last_mut()) {
+            checkpoint.input.push(input.clone());
+        }
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+        self.executed += 1;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Record<W>> {
+        let record = self.records.pop_back()?;
+        self.executed -= 1;
+
+        // A checkpoint of what is now the future is taken again if
+        // execution gets that far
+        let executed = self.executed;
+        self.checkpoints.retain(|c| c.executed <= executed);
+        if record.input.is_some() {
+            if let Some(checkpoint) = self.checkpoints.last_mut() {
+                checkpoint.input.pop();
+            }
+        }
+
+        Some(record)
+    }
+
+    /// Forgets everything since the latest checkpoint from before the
+    /// last instruction, so that the caller can restore it and replay.
+    /// Returns the checkpoint's state, the input consumed since it was
+    /// taken, and the number of instructions to replay.
+    pub(crate) fn rewind(&mut self) -> Option<(Snapshot<W>, Vec<W>, u64)>
+    where
+        W: Clone,
+    {
+        let executed = self.executed;
+        let index = self
+            .checkpoints
+            .iter()
+            .rposition(|c| c.executed < executed)?;
+
+        let later: Vec<_> = self
+            .checkpoints
+            .drain(index + 1..)
+            .flat_map(|c| c.input)
+            .collect();
+        let checkpoint = &mut self.checkpoints[index];
+        let mut input = mem::take(&mut checkpoint.input);
+        input.extend(later);
+
+        self.records.clear();
+        self.executed = checkpoint.executed;
+
+        Some((checkpoint.snapshot.clone(), input, executed - self.executed))
+    }
+
+    /// Every remembered instruction, oldest first.
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &Record<W>> {
+        self.records.iter()
+    }
+
+    /// The number of instructions that can be stepped back over
+    /// without replaying from a checkpoint.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The most recent remembered instruction that wrote to `address`.
+    pub fn last_write_to(&self, address: ProgramCounter) -> Option<&Record<W>> {
+        self.records()
+            .rev()
+            .find(|r| matches!(r.write, Some((a, _, _)) if a == address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Computer, Operation, Parameter, Status};
+
+    // Reads two numbers, multiplies them, adds one and outputs the result
+    const PROGRAM: [i64; 16] = [3, 20, 3, 21, 2, 20, 21, 22, 1001, 22, 1, 22, 4, 22, 99, 0];
+    // Both inputs stored, which grew memory to cover them
+    const PROGRAM_AFTER_INPUT: [i64; 22] = [
+        3, 20, 3, 21, 2, 20, 21, 22, 1001, 22, 1, 22, 4, 22, 99, 0, 0, 0, 0, 0, 2, 3,
+    ];
+
+    #[test]
+    fn finds_the_instruction_behind_an_output() {
+        let mut computer: Computer<Vec<i64>> = Computer::new(PROGRAM.to_vec());
+        computer.record_history(8);
+        computer.push_input(6);
+        computer.push_input(7);
+
+        assert_eq!(computer.run(), Ok(Status::Output(43)));
+
+        let history = computer.history().unwrap();
+        let producer = history.last_write_to(22).unwrap();
+        assert_eq!(producer.pc, 8);
+        assert_eq!(producer.write, Some((22, 42, 43)));
+
+        let multiply = computer.run_back_to(4).unwrap();
+        assert!(matches!(multiply.operation, Operation::Multiply(..)));
+        assert_eq!(computer.pc(), 4);
+        assert_eq!(computer.program.len(), 22);
+
+        // Undoing an input puts it back in the queue, and memory shrinks
+        // back to what it was
+        let input = computer.step_back().unwrap();
+        assert_eq!(input.operation, Operation::Input(Parameter::Position(21)));
+        assert_eq!(computer.program.len(), 21);
+        assert_eq!(computer.run(), Ok(Status::Output(43)));
+    }
+
+    #[test]
+    fn forgets_old_records_but_replays_from_checkpoints() {
+        let mut computer: Computer<Vec<i64>> = Computer::new(PROGRAM.to_vec());
+        computer.record_history(3);
+        computer.push_input(2);
+        computer.push_input(3);
+        assert_eq!(computer.run(), Ok(Status::Output(7)));
+
+        let history = computer.history().unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.records().next().unwrap().pc, 4);
+
+        let input = computer.run_back_to(0).unwrap();
+        assert_eq!(input.input, Some(2));
+        assert_eq!(computer.pc(), 0);
+        assert_eq!(computer.program[..], PROGRAM[..]);
+
+        // Recording started here
+        assert_eq!(computer.step_back(), None);
+        assert_eq!(computer.run(), Ok(Status::Output(7)));
+        assert_eq!(computer.program[..22], PROGRAM_AFTER_INPUT[..]);
+    }
+
+    #[test]
+    fn steps_back_over_many_checkpoints() {
+        // Adds each input to a running total, and outputs the total
+        let program = vec![3, 11, 1, 11, 12, 12, 4, 12, 1105, 1, 0, 0, 0];
+        let mut computer: Computer<Vec<i64>> = Computer::new(program.clone());
+        computer.record_history(3);
+        for i in 1..=10 {
+            computer.push_input(i);
+        }
+
+        let mut outputs = Vec::new();
+        while let Ok(Status::Output(v)) = computer.run() {
+            outputs.push(v);
+        }
+        assert_eq!(outputs.last(), Some(&55));
+
+        // Back to just after the fifth input was added
+        for _ in 0..22 {
+            assert!(computer.step_back().is_some());
+        }
+        assert!(computer.history().unwrap().len() <= 3);
+        assert_eq!(computer.pc(), 6);
+        assert_eq!(computer.program[12], 15);
+
+        let mut outputs = Vec::new();
+        while let Ok(Status::Output(v)) = computer.run() {
+            outputs.push(v);
+        }
+        assert_eq!(outputs, [15, 21, 28, 36, 45, 55]);
+
+        // All the way back, with every input put back
+        while computer.step_back().is_some() {}
+        assert_eq!(computer.pc(), 0);
+        assert_eq!(computer.program, program);
+        assert_eq!(computer.run(), Ok(Status::Output(1)));
+    }
+}
@@ -1,171 +1,328 @@
+use crossbeam_channel::TryRecvError;
 pub use crossbeam_channel::{unbounded as channel, Receiver, Sender};
 use crossbeam_utils::thread;
 use itertools::Itertools;
 use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
     convert::{TryFrom, TryInto},
+    fmt, io,
     str::FromStr,
+    time::Instant,
 };
 
+mod ascii;
+mod assemble;
+mod cache;
+mod cfg;
+mod disassemble;
+mod error;
+mod history;
+mod memory;
+mod network;
+mod output;
+mod parse;
+mod pipeline;
+mod profile;
+mod snapshot;
+#[cfg(feature = "async")]
+mod stream;
+mod trace;
+mod word;
+
+pub use ascii::{Ascii, Text};
+pub use assemble::{assemble, AssemblyError};
+use cache::DecodeCache;
+pub use cfg::{control_flow_graph, Block, Cfg, Edge, EdgeKind};
+pub use disassemble::{disassemble, listing, Line};
+pub use error::Error;
+use error::Fault;
+pub use history::{History, Record};
+pub use memory::{AddressTooLarge, Memory, SparseMemory};
+pub use network::{Control, Event, Network, Packet, NAT};
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigInt;
+pub use output::{Chunks, FnOutput, OutputClosed, Tuple, Writer};
+pub use parse::{load_program, parse_program, parse_words, read_program, LoadError, ParseError};
+pub use pipeline::{NodeId, Pipeline};
+pub use profile::{Loop, Profile};
+pub use snapshot::{Snapshot, SnapshotError};
+#[cfg(feature = "async")]
+pub use stream::Outputs;
+use trace::Tracer;
+pub use word::{ArithmeticPolicy, Word};
+
 pub type Byte = i128;
 pub type Program = Vec<Byte>;
 pub type ProgramCounter = usize;
 pub type ProgramCounterOffset = isize;
 pub type Output = Vec<Byte>;
 
-pub type Error = Box<dyn std::error::Error + Sync + Send + 'static>;
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// The largest relative base that `Operation::execute_fast` works
+/// with, so that adding an offset can't wrap around.
+const MAX_FAST_RELATIVE_BASE: ProgramCounter = isize::MAX as usize;
+
+/// How many instructions run between checks of the deadline.
+const DEADLINE_CHECK_INTERVAL: u64 = 256;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Position = 0,
+    Immediate = 1,
+    Relative = 2,
+}
+
+impl TryFrom<i64> for Mode {
+    type Error = i64;
+
+    fn try_from(mode: i64) -> Result<Self, Self::Error> {
+        match mode {
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            o => Err(o),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum Parameter {
+pub enum Parameter<W = Byte> {
     Position(ProgramCounter),
-    Immediate(Byte),
+    Immediate(W),
     Relative(ProgramCounterOffset),
 }
 
-impl Parameter {
-    fn from_mode_and_value(mode: Byte, value: Byte) -> Result<Self> {
-        match mode {
-            0 => Ok(Parameter::Position(value.try_into()?)),
-            1 => Ok(Parameter::Immediate(value)),
-            2 => Ok(Parameter::Relative(value.try_into()?)),
-            _ => Err(format!("Unknown mode {}", mode))?,
+impl<W> Parameter<W>
+where
+    W: Word,
+{
+    fn from_mode_and_value(mode: i64, value: W) -> Result<Self, Fault<W>> {
+        match Mode::try_from(mode).map_err(Fault::UnknownMode)? {
+            Mode::Position => match value.to_usize() {
+                Some(p) => Ok(Parameter::Position(p)),
+                None => Err(Fault::InvalidAddress(value)),
+            },
+            Mode::Immediate => Ok(Parameter::Immediate(value)),
+            Mode::Relative => match value.to_isize() {
+                Some(r) => Ok(Parameter::Relative(r)),
+                None => Err(Fault::InvalidAddress(value)),
+            },
         }
     }
 
-    fn read(&self, program: &Program, relative_base: ProgramCounter) -> Byte {
-        match *self {
-            Parameter::Position(p) => program.get(p).copied().unwrap_or(0),
-            Parameter::Immediate(i) => i,
-            Parameter::Relative(r) => {
-                let b = isize::try_from(relative_base).expect("Cannot convert relative base");
-                let a = usize::try_from(b + r).expect("Cannot convert absolute address");
-                program.get(a).copied().unwrap_or(0)
-            }
+    pub fn mode(&self) -> Mode {
+        match self {
+            Parameter::Position(..) => Mode::Position,
+            Parameter::Immediate(..) => Mode::Immediate,
+            Parameter::Relative(..) => Mode::Relative,
         }
     }
 
-    fn write(&self, program: &mut Program, relative_base: ProgramCounter, value: Byte) {
+    /// The memory address this parameter refers to, if any. A relative
+    /// parameter that lands outside of memory returns the address it
+    /// computed as the error.
+    pub fn address(&self, relative_base: ProgramCounter) -> Result<Option<ProgramCounter>, W> {
         match *self {
-            Parameter::Position(p) => {
-                if program.len() <= p {
-                    program.resize(p + 1, 0);
-                }
-                program[p] = value
-            }
-            Parameter::Immediate(_) => panic!("Must not write to immediate parameter"),
+            Parameter::Position(p) => Ok(Some(p)),
+            Parameter::Immediate(_) => Ok(None),
             Parameter::Relative(r) => {
-                let b = isize::try_from(relative_base).expect("Cannot convert relative base");
-                let a = usize::try_from(b + r).expect("Cannot convert absolute address");
-                if program.len() <= a {
-                    program.resize(a + 1, 0);
+                // Neither value can reach the limits of an i128
+                let a = relative_base as i128 + r as i128;
+                match a.try_into() {
+                    Ok(a) => Ok(Some(a)),
+                    Err(_) => Err(W::from_i128(a)),
                 }
-                program[a] = value;
             }
         }
     }
+
+    fn read<M>(&self, program: &M, relative_base: ProgramCounter) -> Result<W, Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        match self {
+            Parameter::Immediate(i) => Ok(i.clone()),
+            _ => {
+                let a = self.address(relative_base).map_err(Fault::InvalidAddress)?;
+                Ok(a.map_or_else(W::zero, |a| program.get(a)))
+            }
+        }
+    }
+
+    fn write<M>(
+        &self,
+        program: &mut M,
+        relative_base: ProgramCounter,
+        value: W,
+    ) -> Result<(), Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let a = self
+            .address(relative_base)
+            .map_err(Fault::InvalidAddress)?
+            .ok_or(Fault::WriteToImmediate)?;
+        program
+            .set(a, value)
+            .map_err(|e| Fault::InvalidAddress(W::from_i128(e.0 as i128)))
+    }
+}
+
+fn offset_address<W: Word>(base: ProgramCounter, offset: &W) -> Result<ProgramCounter, Fault<W>> {
+    let a = W::from_i128(base as i128)
+        .checked_add(offset)
+        .ok_or(Fault::Overflow)?;
+    a.to_usize().ok_or(Fault::InvalidAddress(a))
+}
+
+impl<W> fmt::Display for Parameter<W>
+where
+    W: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Position(p) => write!(f, "[{}]", p),
+            Parameter::Immediate(i) => write!(f, "#{}", i),
+            Parameter::Relative(r) if *r < 0 => write!(f, "rb{}", r),
+            Parameter::Relative(r) => write!(f, "rb+{}", r),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-enum Operation {
-    Add(Parameter, Parameter, Parameter),
-    Multiply(Parameter, Parameter, Parameter),
-    Input(Parameter),
-    Output(Parameter),
-    JumpIfTrue(Parameter, Parameter),
-    JumpIfFalse(Parameter, Parameter),
-    LessThan(Parameter, Parameter, Parameter),
-    Equals(Parameter, Parameter, Parameter),
-    AdjustRelativeBase(Parameter),
+pub enum Operation<W = Byte> {
+    Add(Parameter<W>, Parameter<W>, Parameter<W>),
+    Multiply(Parameter<W>, Parameter<W>, Parameter<W>),
+    Input(Parameter<W>),
+    Output(Parameter<W>),
+    JumpIfTrue(Parameter<W>, Parameter<W>),
+    JumpIfFalse(Parameter<W>, Parameter<W>),
+    LessThan(Parameter<W>, Parameter<W>, Parameter<W>),
+    Equals(Parameter<W>, Parameter<W>, Parameter<W>),
+    AdjustRelativeBase(Parameter<W>),
     Halt,
 }
 
-impl Operation {
-    fn decode(program: &Program, pc: ProgramCounter) -> Result<Self, Error> {
+impl<W> Operation<W>
+where
+    W: Word,
+{
+    pub(crate) fn decode<M>(program: &M, pc: ProgramCounter) -> Result<Self, Error<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        Self::decode_fault(program, pc).map_err(|f| f.at(pc, program))
+    }
+
+    fn decode_fault<M>(program: &M, pc: ProgramCounter) -> Result<Self, Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
         use Operation::*;
 
-        let opcode = program[pc] % 100;
+        if pc >= program.len() {
+            return Err(Fault::OutOfBounds);
+        }
+        let opcode = program.get(pc).instruction_digits() % 100;
 
         Ok(match opcode {
-            01 => {
+            1 => {
                 let [l, r, o] = Self::decode_three_params(program, pc)?;
                 Add(l, r, o)
             }
-            02 => {
+            2 => {
                 let [l, r, o] = Self::decode_three_params(program, pc)?;
                 Multiply(l, r, o)
             }
-            03 => {
+            3 => {
                 let [p] = Self::decode_single_param(program, pc)?;
                 Input(p)
             }
-            04 => {
+            4 => {
                 let [p] = Self::decode_single_param(program, pc)?;
                 Output(p)
             }
-            05 => {
+            5 => {
                 let [c, l] = Self::decode_two_params(program, pc)?;
                 JumpIfTrue(c, l)
             }
-            06 => {
+            6 => {
                 let [c, l] = Self::decode_two_params(program, pc)?;
                 JumpIfFalse(c, l)
             }
-            07 => {
+            7 => {
                 let [l, r, o] = Self::decode_three_params(program, pc)?;
                 LessThan(l, r, o)
             }
-            08 => {
+            8 => {
                 let [l, r, o] = Self::decode_three_params(program, pc)?;
                 Equals(l, r, o)
             }
-            09 => {
+            9 => {
                 let [p] = Self::decode_single_param(program, pc)?;
                 AdjustRelativeBase(p)
             }
             99 => Halt,
-            _ => Err(format!("Unknown opcode {}", opcode))?,
+            _ => return Err(Fault::UnknownOpcode),
         })
     }
 
-    fn decode_single_param(program: &Program, pc: ProgramCounter) -> Result<[Parameter; 1]> {
+    fn decode_single_param<M>(
+        program: &M,
+        pc: ProgramCounter,
+    ) -> Result<[Parameter<W>; 1], Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
         let (a,) = Self::params(program, pc)
             .tuples()
             .next()
-            .ok_or("Not enough arguments")?;
+            .ok_or(Fault::OutOfBounds)?;
 
         Ok([a?])
     }
 
-    fn decode_two_params(program: &Program, pc: ProgramCounter) -> Result<[Parameter; 2]> {
+    fn decode_two_params<M>(program: &M, pc: ProgramCounter) -> Result<[Parameter<W>; 2], Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
         let (a, b) = Self::params(program, pc)
             .tuples()
             .next()
-            .ok_or("Not enough arguments")?;
+            .ok_or(Fault::OutOfBounds)?;
 
         Ok([a?, b?])
     }
 
-    fn decode_three_params(program: &Program, pc: ProgramCounter) -> Result<[Parameter; 3]> {
+    fn decode_three_params<M>(
+        program: &M,
+        pc: ProgramCounter,
+    ) -> Result<[Parameter<W>; 3], Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
         let (a, b, c) = Self::params(program, pc)
             .tuples()
             .next()
-            .ok_or("Not enough arguments")?;
+            .ok_or(Fault::OutOfBounds)?;
 
         Ok([a?, b?, c?])
     }
 
-    fn params(
-        program: &Program,
+    fn params<M>(
+        program: &M,
         pc: ProgramCounter,
-    ) -> impl Iterator<Item = Result<Parameter>> + '_ {
-        let (op, args) = program[pc..].split_at(1);
-
-        Self::modes(op[0])
-            .zip(args)
-            .map(|(m, &v)| Parameter::from_mode_and_value(m, v))
+    ) -> impl Iterator<Item = Result<Parameter<W>, Fault<W>>> + '_
+    where
+        M: Memory<Word = W>,
+    {
+        Self::modes(program.get(pc).instruction_digits())
+            .zip(pc + 1..program.len())
+            .map(move |(m, a)| Parameter::from_mode_and_value(m, program.get(a)))
     }
 
-    fn modes(raw_op: Byte) -> impl Iterator<Item = Byte> {
+    fn modes(raw_op: i64) -> impl Iterator<Item = i64> {
         let mut a = 100;
         (0..).map(move |_| {
             let v = raw_op % (a * 10) / a;
@@ -174,96 +331,177 @@ impl Operation {
         })
     }
 
-    fn execute(
+    fn execute<M>(
         &self,
-        program: &mut Program,
+        program: &mut M,
         pc: &mut ProgramCounter,
         relative_base: &mut ProgramCounter,
-        mut input: impl Iterator<Item = Byte>,
-        mut output: impl OutputStream<Item = Byte>,
-    ) -> Result<()> {
+        input: &mut VecDeque<W>,
+        arithmetic: ArithmeticPolicy,
+    ) -> Result<Option<W>, Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
         use Operation::*;
 
+        let mut output = None;
+
         match self {
             Add(l, r, o) => {
-                Self::binop(program, *relative_base, l, r, o, |l, r| l + r);
+                Self::binop(program, *relative_base, l, r, o, |l, r| {
+                    arithmetic.add(&l, &r)
+                })?;
                 *pc += self.width();
             }
             Multiply(l, r, o) => {
-                Self::binop(program, *relative_base, l, r, o, |l, r| l * r);
+                Self::binop(program, *relative_base, l, r, o, |l, r| {
+                    arithmetic.mul(&l, &r)
+                })?;
                 *pc += self.width();
             }
             Input(p) => {
-                let v = input.next().ok_or("No more input is available")?;
-                p.write(program, *relative_base, v);
+                let v = input.front().cloned().ok_or(Fault::InputExhausted)?;
+                p.write(program, *relative_base, v)?;
+                input.pop_front();
                 *pc += self.width();
             }
             Output(p) => {
-                let v = p.read(program, *relative_base);
-                output.push(v);
+                output = Some(p.read(program, *relative_base)?);
                 *pc += self.width();
             }
             JumpIfTrue(c, l) => {
-                if c.read(program, *relative_base) != 0 {
-                    *pc = l.read(program, *relative_base).try_into()?;
+                if c.read(program, *relative_base)? != W::zero() {
+                    *pc = Self::jump_target(program, *relative_base, l)?;
                 } else {
                     *pc += self.width();
                 }
             }
             JumpIfFalse(c, l) => {
-                if c.read(program, *relative_base) == 0 {
-                    *pc = l.read(program, *relative_base).try_into()?;
+                if c.read(program, *relative_base)? == W::zero() {
+                    *pc = Self::jump_target(program, *relative_base, l)?;
                 } else {
                     *pc += self.width();
                 }
             }
             LessThan(l, r, o) => {
-                let v = if l.read(program, *relative_base) < r.read(program, *relative_base) {
-                    1
-                } else {
-                    0
-                };
-                o.write(program, *relative_base, v);
+                Self::binop(program, *relative_base, l, r, o, |l, r| {
+                    Some(W::from_i64((l < r) as i64))
+                })?;
                 *pc += self.width();
             }
             Equals(l, r, o) => {
-                let v = if l.read(program, *relative_base) == r.read(program, *relative_base) {
-                    1
-                } else {
-                    0
-                };
-                o.write(program, *relative_base, v);
+                Self::binop(program, *relative_base, l, r, o, |l, r| {
+                    Some(W::from_i64((l == r) as i64))
+                })?;
                 *pc += self.width();
             }
             AdjustRelativeBase(p) => {
-                let r = p.read(program, *relative_base);
-                let r = isize::try_from(r).expect("Cannot convert relative offset");
-                let b = isize::try_from(*relative_base).expect("Cannot convert relative base");
-                let a = usize::try_from(b + r).expect("Cannot convert absolute address");
-                *relative_base = a;
+                let r = p.read(program, *relative_base)?;
+                *relative_base = offset_address(*relative_base, &r)?;
                 *pc += self.width();
             }
-            Halt => *pc += self.width(),
+            Halt => { /* The program counter stays put so we halt again */ }
         }
 
-        Ok(())
+        Ok(output)
     }
 
-    fn binop(
-        program: &mut Program,
+    /// `execute` for an instruction that only touches cells that
+    /// `memory` already has, doesn't overflow, and isn't input or
+    /// output, skipping most of the checks. Returns the address
+    /// written, the next pc and the next relative base, or `None`
+    /// without changing anything when `execute` is needed instead.
+    ///
+    /// The relative base must be at most `MAX_FAST_RELATIVE_BASE`, and
+    /// stays that way.
+    #[inline]
+    fn execute_fast(
+        &self,
+        memory: &mut [W],
+        pc: ProgramCounter,
         relative_base: ProgramCounter,
-        l: &Parameter,
-        r: &Parameter,
-        o: &Parameter,
-        f: impl FnOnce(Byte, Byte) -> Byte,
-    ) {
-        let l = l.read(program, relative_base);
-        let r = r.read(program, relative_base);
-        let v = f(l, r);
-        o.write(program, relative_base, v);
+    ) -> Option<(Option<ProgramCounter>, ProgramCounter, ProgramCounter)> {
+        use Operation::*;
+
+        let address = |p: &Parameter<W>| {
+            let a = match *p {
+                Parameter::Position(a) => a,
+                // Given the limit on the relative base, this can't wrap
+                // around to a valid address
+                Parameter::Relative(r) => relative_base.wrapping_add(r as usize),
+                Parameter::Immediate(_) => return None,
+            };
+            Some(a).filter(|&a| a < memory.len())
+        };
+        let read = |p: &Parameter<W>| match p {
+            Parameter::Immediate(v) => Some(v.clone()),
+            _ => address(p).map(|a| memory[a].clone()),
+        };
+        let flag = |b: bool| W::from_i64(b.into());
+        let jump = |c: &Parameter<W>, t: &Parameter<W>, when: bool| {
+            if (read(c)? != W::zero()) == when {
+                read(t)?.to_usize()
+            } else {
+                Some(pc + 3)
+            }
+        };
+
+        let (write, next_pc) = match self {
+            Add(l, r, o) => (Some((o, read(l)?.checked_add(&read(r)?)?)), pc + 4),
+            Multiply(l, r, o) => (Some((o, read(l)?.checked_mul(&read(r)?)?)), pc + 4),
+            LessThan(l, r, o) => (Some((o, flag(read(l)? < read(r)?))), pc + 4),
+            Equals(l, r, o) => (Some((o, flag(read(l)? == read(r)?))), pc + 4),
+            JumpIfTrue(c, t) => (None, jump(c, t, true)?),
+            JumpIfFalse(c, t) => (None, jump(c, t, false)?),
+            AdjustRelativeBase(p) => {
+                let r = offset_address(relative_base, &read(p)?).ok()?;
+                let r = Some(r).filter(|&r| r <= MAX_FAST_RELATIVE_BASE)?;
+                return Some((None, pc + 2, r));
+            }
+            Input(..) | Output(..) | Halt => return None,
+        };
+
+        let written = match write {
+            Some((o, v)) => {
+                let a = address(o)?;
+                memory[a] = v;
+                Some(a)
+            }
+            None => None,
+        };
+        Some((written, next_pc, relative_base))
+    }
+
+    fn binop<M>(
+        program: &mut M,
+        relative_base: ProgramCounter,
+        l: &Parameter<W>,
+        r: &Parameter<W>,
+        o: &Parameter<W>,
+        f: impl FnOnce(W, W) -> Option<W>,
+    ) -> Result<(), Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let l = l.read(program, relative_base)?;
+        let r = r.read(program, relative_base)?;
+        let v = f(l, r).ok_or(Fault::Overflow)?;
+        o.write(program, relative_base, v)
+    }
+
+    fn jump_target<M>(
+        program: &M,
+        relative_base: ProgramCounter,
+        target: &Parameter<W>,
+    ) -> Result<ProgramCounter, Fault<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let t = target.read(program, relative_base)?;
+        t.to_usize().ok_or(Fault::InvalidAddress(t))
     }
 
-    fn width(&self) -> ProgramCounter {
+    pub fn width(&self) -> ProgramCounter {
         use Operation::*;
 
         match self {
@@ -279,214 +517,1239 @@ impl Operation {
             Halt => 1,
         }
     }
-}
-
-pub fn parse_program(s: &str) -> Program {
-    s.trim().split(",").flat_map(str::parse).collect()
-}
-
-#[derive(Debug)]
-pub struct Computer {
-    pub program: Program,
-    pc: usize,
-    relative_base: usize,
-}
 
-impl FromStr for Computer {
-    type Err = Error;
+    pub fn mnemonic(&self) -> &'static str {
+        use Operation::*;
 
-    fn from_str(s: &str) -> Result<Computer> {
-        let program: Result<_, _> = s.trim().split(",").map(str::parse).collect();
-        Ok(Self::new(program?))
+        match self {
+            Add(..) => "add",
+            Multiply(..) => "mul",
+            Input(..) => "in",
+            Output(..) => "out",
+            JumpIfTrue(..) => "jt",
+            JumpIfFalse(..) => "jf",
+            LessThan(..) => "lt",
+            Equals(..) => "eq",
+            AdjustRelativeBase(..) => "arb",
+            Halt => "hlt",
+        }
     }
-}
 
-impl Computer {
-    pub fn new(program: Program) -> Self {
-        Self {
-            program,
-            pc: 0,
-            relative_base: 0,
+    pub fn opcode(&self) -> i64 {
+        use Operation::*;
+
+        match self {
+            Add(..) => 1,
+            Multiply(..) => 2,
+            Input(..) => 3,
+            Output(..) => 4,
+            JumpIfTrue(..) => 5,
+            JumpIfFalse(..) => 6,
+            LessThan(..) => 7,
+            Equals(..) => 8,
+            AdjustRelativeBase(..) => 9,
+            Halt => 99,
         }
     }
 
-    pub fn execute(
-        &mut self,
-        input: impl IntoIterator<Item = Byte>,
-        mut output: impl OutputStream<Item = Byte>,
-    ) -> Result<()> {
-        let mut input = input.into_iter();
-
-        loop {
-            let op = Operation::decode(&self.program, self.pc)?;
+    /// The instruction word for this operation, with a mode digit for
+    /// each parameter and nothing else.
+    pub fn instruction(&self) -> i64 {
+        self.parameters()
+            .iter()
+            .zip(&[100, 1_000, 10_000])
+            .map(|(p, scale)| p.mode() as i64 * scale)
+            .sum::<i64>()
+            + self.opcode()
+    }
 
-            op.execute(
-                &mut self.program,
-                &mut self.pc,
-                &mut self.relative_base,
-                &mut input,
-                &mut output,
-            )?;
+    pub fn parameters(&self) -> Vec<Parameter<W>> {
+        use Operation::*;
 
-            if op == Operation::Halt {
-                break;
+        match self {
+            Add(a, b, c) | Multiply(a, b, c) | LessThan(a, b, c) | Equals(a, b, c) => {
+                vec![a.clone(), b.clone(), c.clone()]
             }
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => vec![a.clone(), b.clone()],
+            Input(a) | Output(a) | AdjustRelativeBase(a) => vec![a.clone()],
+            Halt => vec![],
         }
+    }
 
-        Ok(())
+    /// The parameters that this operation reads from.
+    pub fn sources(&self) -> Vec<Parameter<W>> {
+        let mut parameters = self.parameters();
+        if self.destination().is_some() {
+            parameters.pop();
+        }
+        parameters
     }
 
-    pub fn execute_side_by_side<F, T>(&mut self, f: F) -> T
-    where
-        T: Send + Sync,
-        F: FnOnce(Sender<Byte>, Receiver<Byte>) -> T,
-        F: Send + Sync,
-    {
-        let (tx, rx) = channel();
-        let (tx2, rx2) = channel();
+    /// The parameter that this operation writes to, if any.
+    pub fn destination(&self) -> Option<&Parameter<W>> {
+        use Operation::*;
 
-        thread::scope(|s| {
-            let side_program = s.spawn(move |_| f(tx2, rx));
+        match self {
+            Add(_, _, o) | Multiply(_, _, o) | LessThan(_, _, o) | Equals(_, _, o) => Some(o),
+            Input(p) => Some(p),
+            _ => None,
+        }
+    }
+}
 
-            let computer = s.spawn(move |_| {
-                let v = self.execute(rx2, tx);
-                v
-            });
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Status<W = Byte> {
+    NeedsInput,
+    Output(W),
+    Halted,
+    /// The instruction at this address is about to execute.
+    Breakpoint(ProgramCounter),
+    /// The instruction limit or deadline was reached. Raising the
+    /// limit or moving the deadline allows execution to resume.
+    BudgetExhausted,
+    /// The instruction at `pc` has executed and accessed a watched
+    /// address. `value` is the value read or written.
+    Watchpoint {
+        pc: ProgramCounter,
+        address: ProgramCounter,
+        access: Access,
+        value: W,
+    },
+}
 
-            computer
-                .join()
-                .expect("Computer panicked")
-                .expect("Execution failed");
-            let side_result = side_program.join().expect("Side program panicked");
+/// How a program run alongside a controller finished.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Halted,
+    /// The controller returned while the program was still waiting
+    /// to exchange values with it.
+    Cancelled,
+}
 
-            side_result
-        })
-        .expect("Unable to run side-by-side program")
-    }
+/// The `Status`es that matter to code driving a program through its
+/// input and output.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Io<W> {
+    NeedsInput,
+    Output(W),
+    Halted,
 }
 
-pub fn execute_with_output(
-    program: &mut Program,
-    input: impl IntoIterator<Item = Byte>,
-    output: impl OutputStream<Item = Byte>,
-) -> Result<()> {
-    let inner_program = std::mem::replace(program, Program::new());
-    let mut computer = Computer::new(inner_program);
-    let r = computer.execute(input, output);
-    std::mem::swap(program, &mut computer.program);
-    r
+/// The kinds of memory access that a watchpoint stops on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
 }
 
-pub fn execute_side_by_side<F, T>(program: &mut Program, f: F) -> T
+impl Access {
+    fn includes(self, other: Access) -> bool {
+        self == Access::ReadWrite || self == other
+    }
+}
+
+impl<W> fmt::Display for Operation<W>
 where
-    T: Send + Sync,
-    F: FnOnce(Sender<Byte>, Receiver<Byte>) -> T,
-    F: Send + Sync,
+    W: Word,
 {
-    let inner_program = std::mem::replace(program, Program::new());
-    let mut computer = Computer::new(inner_program);
-    let r = computer.execute_side_by_side(f);
-    std::mem::swap(program, &mut computer.program);
-    r
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())?;
+        for (i, p) in self.parameters().iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, p)?;
+        }
+        Ok(())
+    }
 }
 
-pub fn execute(program: &mut Program, input: impl IntoIterator<Item = Byte>) -> Result<Output> {
-    let mut output = Output::default();
-    execute_with_output(program, input, &mut output).map(|()| output)
+/// A description of a single executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step<W = Byte> {
+    pub pc: ProgramCounter,
+    pub operation: Operation<W>,
+    /// The value of each parameter, in order. Parameters that are
+    /// written to resolve to their address instead.
+    pub operands: Vec<W>,
+    /// The address and value written by the instruction, if any.
+    pub write: Option<(ProgramCounter, W)>,
+    pub next_pc: ProgramCounter,
+    /// Set when the instruction paused execution, as it would for `run`.
+    pub status: Option<Status<W>>,
 }
 
-pub trait OutputStream {
-    type Item;
-    fn push(&mut self, val: Self::Item);
+/// An intcode machine. The memory defaults to a `Vec`; pass a
+/// `SparseMemory` to `new` for programs that use distant addresses.
+/// The memory also decides the word type.
+///
+/// Memory grows to cover whatever the program writes, as far as it can
+/// be allocated. Use `set_memory_limit` to bound it further.
+#[derive(Debug, Clone)]
+pub struct Computer<M: Memory = Program> {
+    pub program: M,
+    pc: usize,
+    relative_base: usize,
+    input: VecDeque<M::Word>,
+    arithmetic: ArithmeticPolicy,
+    history: Option<History<M::Word>>,
+    tracer: Option<Tracer>,
+    breakpoints: BTreeSet<ProgramCounter>,
+    watchpoints: BTreeMap<ProgramCounter, Access>,
+    /// The breakpoint we last stopped at, which must not stop us again.
+    resume_from: Option<ProgramCounter>,
+    /// A stop queued behind another one from the same instruction,
+    /// waiting to be reported.
+    pending: Option<Status<M::Word>>,
+    profile: Option<Box<Profile>>,
+    retired: u64,
+    /// How many more instructions `run` may execute.
+    instruction_limit: Option<u64>,
+    deadline: Option<Instant>,
+    /// Writes may not grow memory to this many words or more.
+    memory_limit: Option<usize>,
+    decoded: DecodeCache<M::Word>,
 }
 
-impl<O> OutputStream for &'_ mut O
+impl<W> FromStr for Computer<Vec<W>>
 where
-    O: OutputStream,
+    W: Word,
 {
-    type Item = O::Item;
+    type Err = ParseError;
 
-    fn push(&mut self, val: Self::Item) {
-        (**self).push(val);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_words(s).map(Self::new)
     }
 }
 
-impl<T> OutputStream for Vec<T> {
-    type Item = T;
-
-    fn push(&mut self, val: Self::Item) {
-        Vec::push(self, val);
+/// Writes the regions of a snapshot into empty memory, and grows it to
+/// `length`.
+fn load<M: Memory>(
+    program: &mut M,
+    regions: Vec<(ProgramCounter, Vec<M::Word>)>,
+    length: usize,
+) -> Result<(), AddressTooLarge> {
+    for (start, values) in regions {
+        for (offset, value) in values.into_iter().enumerate() {
+            let address = start
+                .checked_add(offset)
+                .ok_or(AddressTooLarge(ProgramCounter::MAX))?;
+            program.set(address, value)?;
+        }
+    }
+    if program.len() < length {
+        program.set(length - 1, M::Word::zero())?;
     }
+    Ok(())
 }
 
-impl<T> OutputStream for Sender<T> {
-    type Item = T;
+impl<M> Computer<M>
+where
+    M: Memory,
+{
+    pub fn new(program: M) -> Self {
+        Self {
+            program,
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            arithmetic: ArithmeticPolicy::default(),
+            history: None,
+            tracer: None,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            resume_from: None,
+            pending: None,
+            profile: None,
+            retired: 0,
+            instruction_limit: None,
+            deadline: None,
+            memory_limit: None,
+            decoded: DecodeCache::default(),
+        }
+    }
 
-    fn push(&mut self, val: Self::Item) {
-        self.send(val).expect("Unable to output to channel");
+    pub fn push_input(&mut self, value: M::Word) {
+        self.input.push_back(value);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn pc(&self) -> ProgramCounter {
+        self.pc
+    }
 
-    #[test]
-    fn specifications_day_02() -> Result<()> {
-        let mut state = vec![1, 0, 0, 0, 99];
-        execute(&mut state, None)?;
-        assert_eq!(state, [2, 0, 0, 0, 99]);
+    pub fn relative_base(&self) -> ProgramCounter {
+        self.relative_base
+    }
 
-        let mut state = vec![2, 3, 0, 3, 99];
-        execute(&mut state, None)?;
-        assert_eq!(state, [2, 3, 0, 6, 99]);
+    pub fn arithmetic_policy(&self) -> ArithmeticPolicy {
+        self.arithmetic
+    }
 
-        let mut state = vec![2, 4, 4, 5, 99, 0];
-        execute(&mut state, None)?;
-        assert_eq!(state, [2, 4, 4, 5, 99, 9801]);
+    /// Chooses what `Add` and `Multiply` do when the result overflows
+    /// the word. Defaults to `ArithmeticPolicy::Checked`.
+    pub fn set_arithmetic_policy(&mut self, policy: ArithmeticPolicy) {
+        self.arithmetic = policy;
+    }
 
-        let mut state = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-        execute(&mut state, None)?;
-        assert_eq!(state, [30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    /// Decoded instructions are cached so that loops run faster. This
+    /// turns that off, or back on, which is only useful to measure the
+    /// difference.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded.reset(!enabled);
+    }
 
-        Ok(())
+    /// Captures the complete state of the machine. Runs of zeros at the
+    /// edges of each memory region are left out.
+    pub fn snapshot(&self) -> Snapshot<M::Word> {
+        let zero = M::Word::zero();
+        let memory = self
+            .program
+            .regions()
+            .into_iter()
+            .filter_map(|(start, values)| {
+                let first = values.iter().position(|v| *v != zero)?;
+                let last = values.iter().rposition(|v| *v != zero)?;
+                Some((start + first, values[first..=last].to_vec()))
+            })
+            .collect();
+
+        Snapshot {
+            pc: self.pc,
+            relative_base: self.relative_base,
+            arithmetic: self.arithmetic,
+            input: self.input.iter().cloned().collect(),
+            length: self.program.len(),
+            memory,
+        }
     }
 
-    #[test]
-    fn specifications_day_05() -> Result<()> {
-        let mut program = vec![1002, 4, 3, 4, 33];
-        execute(&mut program, None)?;
-        assert_eq!(program[4], 99);
+    /// Starts recording every instruction so that it can be undone
+    /// with `step_back`. Only the most recent `limit` instructions are
+    /// kept, along with a checkpoint every `limit` instructions that
+    /// older ones are replayed from. Any existing history is discarded.
+    ///
+    /// Changes made to `program` directly aren't recorded, so replaying
+    /// doesn't repeat them.
+    pub fn record_history(&mut self, limit: usize) {
+        self.history = Some(History::new(limit));
+    }
 
-        let mut program = vec![3, 0, 4, 0, 99];
-        let output = execute(&mut program, Some(42))?;
-        assert_eq!(output, [42]);
+    pub fn stop_recording_history(&mut self) -> Option<History<M::Word>> {
+        self.history.take()
+    }
 
-        Ok(())
+    pub fn history(&self) -> Option<&History<M::Word>> {
+        self.history.as_ref()
     }
 
-    #[test]
-    fn compare_instructions() -> Result<()> {
-        let mut program = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
-        let output = execute(&mut program, Some(8))?;
-        assert_eq!(output, [1]);
+    /// Undoes the most recently executed instruction, returning what
+    /// it did. Returns `None` when history isn't being recorded or
+    /// doesn't reach back any further.
+    pub fn step_back(&mut self) -> Option<Record<M::Word>> {
+        if self.history.as_ref()?.is_empty() {
+            self.replay_from_checkpoint()?;
+        }
+        let record = self.history.as_mut()?.pop()?;
+
+        if let Some((address, ref old, _)) = record.write {
+            self.program
+                .set(address, old.clone())
+                .expect("The address was written before");
+            self.program.truncate(record.memory_len);
+            self.decoded.invalidate(address);
+        }
+        if let Some(input) = &record.input {
+            self.input.push_front(input.clone());
+        }
+        self.pc = record.pc;
+        self.relative_base = record.relative_base;
 
-        let mut program = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
-        let output = execute(&mut program, Some(8))?;
-        assert_eq!(output, [0]);
+        Some(record)
+    }
 
-        let mut program = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
-        let output = execute(&mut program, Some(7))?;
-        assert_eq!(output, [0]);
+    /// Restores the latest checkpoint from before the last instruction
+    /// and replays forward to where we were, so that there are records
+    /// to step back over again.
+    fn replay_from_checkpoint(&mut self) -> Option<()> {
+        let (snapshot, input, count) = self.history.as_mut()?.rewind()?;
+
+        self.program.truncate(0);
+        load(&mut self.program, snapshot.memory, snapshot.length)
+            .expect("The checkpoint was taken from this memory");
+        self.decoded = DecodeCache::default();
+        self.pc = snapshot.pc;
+        self.relative_base = snapshot.relative_base;
+        for value in input.into_iter().rev() {
+            self.input.push_front(value);
+        }
 
-        let mut program = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
-        let output = execute(&mut program, Some(7))?;
-        assert_eq!(output, [1]);
+        for _ in 0..count {
+            let pc = self.pc;
+            let relative_base = self.relative_base;
+            let op = self
+                .decoded
+                .decode(&self.program, pc)
+                .expect("The instruction executed before")
+                .clone();
+
+            let before = self.prepare_record(&op);
+            let memory_len = self.program.len();
+            op.execute(
+                &mut self.program,
+                &mut self.pc,
+                &mut self.relative_base,
+                &mut self.input,
+                self.arithmetic,
+            )
+            .expect("The instruction executed before");
 
-        Ok(())
+            if let Some(Ok(Some(a))) = op.destination().map(|p| p.address(relative_base)) {
+                self.decoded.invalidate(a);
+            }
+            self.push_record(pc, relative_base, &op, before, memory_len);
+        }
+
+        Some(())
     }
 
-    #[test]
+    /// Steps back until the instruction at `pc` is the next to execute,
+    /// returning the record of that instruction. When the history runs
+    /// out first, the machine is left at the earliest remembered state
+    /// and `None` is returned.
+    pub fn run_back_to(&mut self, pc: ProgramCounter) -> Option<Record<M::Word>> {
+        loop {
+            let record = self.step_back()?;
+            if record.pc == pc {
+                return Some(record);
+            }
+        }
+    }
+
+    /// Rebuilds a machine from a snapshot, ready to resume. Fails when
+    /// the snapshot covers addresses that `M` can't hold.
+    pub fn restore(snapshot: Snapshot<M::Word>) -> Result<Self, AddressTooLarge>
+    where
+        M: Default,
+    {
+        let mut program = M::default();
+        load(&mut program, snapshot.memory, snapshot.length)?;
+
+        let mut computer = Self::new(program);
+        computer.pc = snapshot.pc;
+        computer.relative_base = snapshot.relative_base;
+        computer.input = snapshot.input.into();
+        computer.arithmetic = snapshot.arithmetic;
+        Ok(computer)
+    }
+
+    /// Runs until the program needs input that hasn't been pushed,
+    /// produces a value, halts, or reaches a breakpoint or watchpoint.
+    /// Calling this again resumes where the previous call stopped.
+    ///
+    /// When an `Output` instruction also hits a watchpoint, the output
+    /// is returned first and the watchpoint by the following call.
+    pub fn run(&mut self) -> Result<Status<M::Word>, Error<M::Word>> {
+        if let Some(status) = self.pending.take() {
+            return Ok(status);
+        }
+        self.decoded.start_epoch();
+
+        if !self.instrumented() {
+            return self.run_plain();
+        }
+
+        loop {
+            if self.breakpoints.contains(&self.pc) && self.resume_from != Some(self.pc) {
+                self.resume_from = Some(self.pc);
+                return Ok(Status::Breakpoint(self.pc));
+            }
+
+            let op = self.decoded.decode(&self.program, self.pc)?.clone();
+
+            if self.budget_exhausted() {
+                return Ok(Status::BudgetExhausted);
+            }
+
+            if let Some(status) = self.execute_operation(&op)? {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// `run`, for drivers that only exchange input and output: it
+    /// carries on past breakpoints and watchpoints, and reports running
+    /// out of budget as an error.
+    pub(crate) fn run_until_io(&mut self) -> Result<Io<M::Word>, Error<M::Word>> {
+        loop {
+            match self.run()? {
+                Status::NeedsInput => return Ok(Io::NeedsInput),
+                Status::Output(v) => return Ok(Io::Output(v)),
+                Status::Halted => return Ok(Io::Halted),
+                Status::Breakpoint(..) | Status::Watchpoint { .. } => {}
+                Status::BudgetExhausted => {
+                    return Err(Fault::BudgetExhausted.at(self.pc, &self.program))
+                }
+            }
+        }
+    }
+
+    /// Whether anything needs to see each instruction as it executes.
+    fn instrumented(&self) -> bool {
+        self.history.is_some()
+            || self.tracer.is_some()
+            || self.profile.is_some()
+            || !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || self.instruction_limit.is_some()
+            || self.deadline.is_some()
+            || self.memory_limit.is_some()
+    }
+
+    /// `run` without the checks that `instrumented` covers.
+    fn run_plain(&mut self) -> Result<Status<M::Word>, Error<M::Word>> {
+        self.resume_from = None;
+
+        loop {
+            self.run_fast();
+
+            let pc = self.pc;
+            let relative_base = self.relative_base;
+            let op = self.decoded.decode(&self.program, pc)?;
+
+            match op {
+                Operation::Input(_) if self.input.is_empty() => return Ok(Status::NeedsInput),
+                Operation::Halt => return Ok(Status::Halted),
+                _ => {}
+            }
+
+            let output = op.execute(
+                &mut self.program,
+                &mut self.pc,
+                &mut self.relative_base,
+                &mut self.input,
+                self.arithmetic,
+            );
+            let written = op.destination().map(|p| p.address(relative_base));
+
+            let output = output.map_err(|f| f.at(pc, &self.program))?;
+            self.retired += 1;
+
+            if let Some(Ok(Some(a))) = written {
+                self.decoded.invalidate(a);
+            }
+
+            if let Some(v) = output {
+                return Ok(Status::Output(v));
+            }
+        }
+    }
+
+    /// Executes instructions for as long as they are already decoded
+    /// and `Operation::execute_fast` can handle them, which is almost
+    /// all of them.
+    fn run_fast(&mut self) {
+        let memory = match self.program.as_mut_slice() {
+            Some(memory) => memory,
+            None => return,
+        };
+        if self.relative_base > MAX_FAST_RELATIVE_BASE {
+            return;
+        }
+
+        let mut pc = self.pc;
+        let mut relative_base = self.relative_base;
+        let mut retired = 0;
+
+        while let Some(op) = self.decoded.cached(pc) {
+            let (written, next_pc, next_relative_base) =
+                match op.execute_fast(memory, pc, relative_base) {
+                    Some(step) => step,
+                    None => break,
+                };
+            if let Some(a) = written {
+                self.decoded.invalidate(a);
+            }
+            pc = next_pc;
+            relative_base = next_relative_base;
+            retired += 1;
+        }
+
+        self.pc = pc;
+        self.relative_base = relative_base;
+        self.retired += retired;
+    }
+
+    fn budget_exhausted(&self) -> bool {
+        if self.instruction_limit == Some(0) {
+            return true;
+        }
+        // Reading the clock costs far more than an instruction
+        match self.deadline {
+            Some(deadline) => {
+                self.retired % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline
+            }
+            None => false,
+        }
+    }
+
+    /// The number of instructions executed so far.
+    pub fn instructions_retired(&self) -> u64 {
+        self.retired
+    }
+
+    /// Makes `run` return `Status::BudgetExhausted` after executing
+    /// `limit` more instructions. `None` removes the limit.
+    pub fn set_instruction_limit(&mut self, limit: Option<u64>) {
+        self.instruction_limit = limit;
+    }
+
+    /// The number of instructions `run` may still execute.
+    pub fn instruction_limit(&self) -> Option<u64> {
+        self.instruction_limit
+    }
+
+    /// Makes instructions that would write at or beyond `limit`, and
+    /// so grow memory to more than `limit` words, fail with
+    /// `Error::AddressOutOfRange`. Memory that is already that long can
+    /// still be written. `None`, the default, removes the limit.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Makes `run` return `Status::BudgetExhausted` once `deadline`
+    /// has passed. The clock is only checked every few hundred
+    /// instructions. `None` removes the deadline.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Starts counting executed instructions and memory accesses,
+    /// discarding any previous profile.
+    pub fn start_profiling(&mut self) {
+        self.profile = Some(Box::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take().map(|p| *p)
+    }
+
+    /// Stops `run` before the instruction at `pc` executes.
+    pub fn add_breakpoint(&mut self, pc: ProgramCounter) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: ProgramCounter) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = ProgramCounter> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stops `run` after an instruction reads or writes `address`, as
+    /// chosen by `access`. Replaces any watchpoint on that address.
+    pub fn add_watchpoint(&mut self, address: ProgramCounter, access: Access) {
+        self.watchpoints.insert(address, access);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: ProgramCounter) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (ProgramCounter, Access)> + '_ {
+        self.watchpoints.iter().map(|(&a, &k)| (a, k))
+    }
+
+    /// Executes exactly one instruction, ignoring breakpoints. An
+    /// `Input` instruction with no pending input and a `Halt`
+    /// instruction leave the program counter where it was.
+    ///
+    /// A stop still waiting to be reported, such as a watchpoint
+    /// queued behind an output, is returned first. Like waiting for
+    /// input, that doesn't execute anything.
+    pub fn step(&mut self) -> Result<Step<M::Word>, Error<M::Word>> {
+        self.decoded.start_epoch();
+        let pc = self.pc;
+        let operation = self.decoded.decode(&self.program, pc)?.clone();
+        let (operands, destination) = self
+            .operands(&operation)
+            .map_err(|f| f.at(pc, &self.program))?;
+
+        if let Some(status) = self.pending.take() {
+            return Ok(Step {
+                pc,
+                operation,
+                operands,
+                write: None,
+                next_pc: pc,
+                status: Some(status),
+            });
+        }
+
+        let status = self.execute_operation(&operation)?;
+
+        let write = match status {
+            Some(Status::NeedsInput) => None,
+            _ => destination.map(|a| (a, self.program.get(a))),
+        };
+
+        Ok(Step {
+            pc,
+            operation,
+            operands,
+            write,
+            next_pc: self.pc,
+            status,
+        })
+    }
+
+    /// The value of each parameter of `op` and the address it writes
+    /// to. The destination is always the last parameter and resolves
+    /// to its address.
+    #[allow(clippy::type_complexity)]
+    fn operands(
+        &self,
+        op: &Operation<M::Word>,
+    ) -> Result<(Vec<M::Word>, Option<ProgramCounter>), Fault<M::Word>> {
+        let relative_base = self.relative_base;
+        let parameters = op.parameters();
+        let destination = match op.destination() {
+            Some(p) => p.address(relative_base).map_err(Fault::InvalidAddress)?,
+            None => None,
+        };
+        let operands = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match destination {
+                Some(a) if i == parameters.len() - 1 => Ok(M::Word::from_i128(a as i128)),
+                _ => p.read(&self.program, relative_base),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok((operands, destination))
+    }
+
+    /// Writes a JSON Lines record of every instruction executed from
+    /// now on to `writer`, replacing any existing tracer.
+    pub fn trace_to(&mut self, writer: impl io::Write + Send + 'static) {
+        self.tracer = Some(Tracer::new(writer));
+    }
+
+    /// Stops tracing and flushes the writer. Returns the first error
+    /// encountered while writing the trace, if any.
+    pub fn stop_tracing(&mut self) -> io::Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn execute_operation(
+        &mut self,
+        op: &Operation<M::Word>,
+    ) -> Result<Option<Status<M::Word>>, Error<M::Word>> {
+        let pc = self.pc;
+        let relative_base = self.relative_base;
+
+        if let Operation::Input(_) = op {
+            if self.input.is_empty() {
+                return Ok(Some(Status::NeedsInput));
+            }
+        }
+
+        let traced = match &self.tracer {
+            Some(_) => self.operands(op).ok(),
+            None => None,
+        };
+
+        if let Operation::Halt = op {
+            if let (Some(tracer), Some((operands, _))) = (&self.tracer, &traced) {
+                tracer.record(pc, op, operands, None, relative_base);
+            }
+            return Ok(Some(Status::Halted));
+        }
+
+        let reads = if self.watchpoints.is_empty() {
+            Vec::new()
+        } else {
+            self.watched_reads(op)
+        };
+
+        if matches!(&self.history, Some(h) if h.wants_checkpoint()) {
+            let snapshot = self.snapshot();
+            if let Some(history) = &mut self.history {
+                history.checkpoint(snapshot);
+            }
+        }
+        let before = match &self.history {
+            Some(_) => Some(self.prepare_record(op)),
+            None => None,
+        };
+        let memory_len = self.program.len();
+
+        if let Some(limit) = self.memory_limit {
+            if let Some(Ok(Some(a))) = op.destination().map(|p| p.address(relative_base)) {
+                if a >= limit && a >= memory_len {
+                    let address = M::Word::from_i128(a as i128);
+                    return Err(Fault::InvalidAddress(address).at(pc, &self.program));
+                }
+            }
+        }
+
+        let output = op
+            .execute(
+                &mut self.program,
+                &mut self.pc,
+                &mut self.relative_base,
+                &mut self.input,
+                self.arithmetic,
+            )
+            .map_err(|f| f.at(pc, &self.program))?;
+
+        if let Some(Ok(Some(a))) = op.destination().map(|p| p.address(relative_base)) {
+            self.decoded.invalidate(a);
+        }
+
+        if let (Some(tracer), Some((operands, destination))) = (&self.tracer, &traced) {
+            let value = destination.map(|a| (a, self.program.get(a)));
+            let write = value.as_ref().map(|(a, v)| (*a, v));
+            tracer.record(pc, op, operands, write, relative_base);
+        }
+
+        if let Some(before) = before {
+            self.push_record(pc, relative_base, op, before, memory_len);
+        }
+
+        self.resume_from = None;
+        self.retired += 1;
+        if let Some(limit) = &mut self.instruction_limit {
+            *limit = limit.saturating_sub(1);
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.instructions += 1;
+            Profile::count(&mut profile.by_pc, pc);
+            *profile.by_mnemonic.entry(op.mnemonic()).or_insert(0) += 1;
+            for p in op.sources() {
+                if let Ok(Some(a)) = p.address(relative_base) {
+                    Profile::count(&mut profile.reads, a);
+                }
+            }
+            if let Some(Ok(Some(a))) = op.destination().map(|p| p.address(relative_base)) {
+                Profile::count(&mut profile.writes, a);
+            }
+            if self.pc < pc {
+                *profile.backward_jumps.entry((self.pc, pc)).or_insert(0) += 1;
+            }
+        }
+
+        let read = reads
+            .into_iter()
+            .next()
+            .map(|(address, value)| Status::Watchpoint {
+                pc,
+                address,
+                access: Access::Read,
+                value,
+            });
+        let write = self.watched_write(pc, op, relative_base);
+
+        // An instruction that outputs doesn't write, so at most two of
+        // these happen at once: report one and queue the other
+        let mut stops = output
+            .map(Status::Output)
+            .into_iter()
+            .chain(read)
+            .chain(write);
+        let status = stops.next();
+        self.pending = stops.next();
+        Ok(status)
+    }
+
+    /// The watched addresses, and their values, that `op` is about to read.
+    fn watched_reads(&self, op: &Operation<M::Word>) -> Vec<(ProgramCounter, M::Word)> {
+        op.sources()
+            .iter()
+            .filter_map(|p| p.address(self.relative_base).ok().flatten())
+            .filter(|a| {
+                self.watchpoints
+                    .get(a)
+                    .is_some_and(|k| k.includes(Access::Read))
+            })
+            .map(|a| (a, self.program.get(a)))
+            .collect()
+    }
+
+    /// The watchpoint hit by the write `op` just made, if any.
+    fn watched_write(
+        &self,
+        pc: ProgramCounter,
+        op: &Operation<M::Word>,
+        relative_base: ProgramCounter,
+    ) -> Option<Status<M::Word>> {
+        let address = op.destination()?.address(relative_base).ok()??;
+        let access = self.watchpoints.get(&address)?;
+
+        if access.includes(Access::Write) {
+            Some(Status::Watchpoint {
+                pc,
+                address,
+                access: Access::Write,
+                value: self.program.get(address),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The address and current value of the cell `op` will write, and
+    /// the input it will consume.
+    #[allow(clippy::type_complexity)]
+    fn prepare_record(
+        &self,
+        op: &Operation<M::Word>,
+    ) -> (Option<(ProgramCounter, M::Word)>, Option<M::Word>) {
+        let write = op
+            .destination()
+            .and_then(|p| p.address(self.relative_base).ok().flatten())
+            .map(|a| (a, self.program.get(a)));
+        let input = match op {
+            Operation::Input(_) => self.input.front().cloned(),
+            _ => None,
+        };
+        (write, input)
+    }
+
+    /// Completes the record of an instruction that has just executed.
+    #[allow(clippy::type_complexity)]
+    fn push_record(
+        &mut self,
+        pc: ProgramCounter,
+        relative_base: ProgramCounter,
+        op: &Operation<M::Word>,
+        (write, input): (Option<(ProgramCounter, M::Word)>, Option<M::Word>),
+        memory_len: usize,
+    ) {
+        let write = write.map(|(a, old)| (a, old, self.program.get(a)));
+        let record = Record {
+            pc,
+            relative_base,
+            operation: op.clone(),
+            write,
+            input,
+            memory_len,
+            next_pc: self.pc,
+            next_relative_base: self.relative_base,
+        };
+        if let Some(history) = &mut self.history {
+            history.push(record);
+        }
+    }
+
+    pub fn execute(
+        &mut self,
+        input: impl IntoIterator<Item = M::Word>,
+        output: impl OutputStream<Item = M::Word>,
+    ) -> Result<(), Error<M::Word>> {
+        // An iterator never blocks, so this only stops once halted
+        let mut input = IterInput(input.into_iter());
+        self.execute_until_blocked(&mut input, output).map(drop)
+    }
+
+    /// Runs until the program halts, returning `Status::Halted`, or
+    /// until it needs input that `input` doesn't have yet, returning
+    /// `Status::NeedsInput`. Calling again carries on from there.
+    pub fn execute_until_blocked(
+        &mut self,
+        input: &mut impl InputStream<Item = M::Word>,
+        mut output: impl OutputStream<Item = M::Word>,
+    ) -> Result<Status<M::Word>, Error<M::Word>> {
+        loop {
+            match self.run_until_io()? {
+                Io::NeedsInput => match input.next_input() {
+                    Input::Value(v) => self.push_input(v),
+                    Input::WouldBlock => return Ok(Status::NeedsInput),
+                    Input::Closed => return Err(Fault::InputExhausted.at(self.pc, &self.program)),
+                },
+                Io::Output(v) => {
+                    if output.push(v).is_err() {
+                        // Report the output instruction, which has executed
+                        let pc = self.pc - 2;
+                        return Err(Fault::OutputClosed.at(pc, &self.program));
+                    }
+                }
+                Io::Halted => return Ok(Status::Halted),
+            }
+        }
+    }
+
+    /// Runs the program on its own thread while `f` drives it through
+    /// a pair of channels, returning what `f` returns. Panics if the
+    /// program fails; see `try_execute_side_by_side` to handle that.
+    pub fn execute_side_by_side<F, T>(&mut self, f: F) -> T
+    where
+        M: Send,
+        T: Send + Sync,
+        F: FnOnce(Sender<M::Word>, Receiver<M::Word>) -> T,
+        F: Send + Sync,
+    {
+        let (t, outcome) = self.try_execute_side_by_side(f);
+        outcome.expect("Execution failed");
+        t
+    }
+
+    /// Like `execute_side_by_side`, but also reports how the program
+    /// finished. `f` may return at any point: a program that is still
+    /// waiting for input or trying to output then stops with
+    /// `Outcome::Cancelled`. Values that `f` sends after the program
+    /// has finished are ignored.
+    pub fn try_execute_side_by_side<F, T>(&mut self, f: F) -> (T, Result<Outcome, Error<M::Word>>)
+    where
+        M: Send,
+        T: Send + Sync,
+        F: FnOnce(Sender<M::Word>, Receiver<M::Word>) -> T,
+        F: Send + Sync,
+    {
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel();
+
+        // Held open until both sides finish so that sending never fails
+        let input = &rx2;
+
+        thread::scope(|s| {
+            let side_program = s.spawn(move |_| f(tx2, rx));
+
+            let computer = s.spawn(move |_| self.converse(input, tx));
+
+            let outcome = computer.join().expect("Computer panicked");
+            let t = side_program.join().expect("Side program panicked");
+            (t, outcome)
+        })
+        .expect("Unable to run side-by-side program")
+    }
+
+    fn converse(
+        &mut self,
+        input: &Receiver<M::Word>,
+        output: Sender<M::Word>,
+    ) -> Result<Outcome, Error<M::Word>> {
+        loop {
+            match self.run_until_io()? {
+                Io::NeedsInput => match input.recv() {
+                    Ok(v) => self.push_input(v),
+                    Err(_) => return Ok(Outcome::Cancelled),
+                },
+                Io::Output(v) => {
+                    if output.send(v).is_err() {
+                        return Ok(Outcome::Cancelled);
+                    }
+                }
+                Io::Halted => return Ok(Outcome::Halted),
+            }
+        }
+    }
+}
+
+pub fn execute_with_output(
+    program: &mut Program,
+    input: impl IntoIterator<Item = Byte>,
+    output: impl OutputStream<Item = Byte>,
+) -> Result<()> {
+    let inner_program = std::mem::take(program);
+    let mut computer = Computer::new(inner_program);
+    let r = computer.execute(input, output);
+    std::mem::swap(program, &mut computer.program);
+    r
+}
+
+pub fn execute_side_by_side<F, T>(program: &mut Program, f: F) -> T
+where
+    T: Send + Sync,
+    F: FnOnce(Sender<Byte>, Receiver<Byte>) -> T,
+    F: Send + Sync,
+{
+    let inner_program = std::mem::take(program);
+    let mut computer = Computer::new(inner_program);
+    let r = computer.execute_side_by_side(f);
+    std::mem::swap(program, &mut computer.program);
+    r
+}
+
+pub fn execute(program: &mut Program, input: impl IntoIterator<Item = Byte>) -> Result<Output> {
+    let mut output = Vec::new();
+    execute_with_output(program, input, &mut output).map(|()| output)
+}
+
+/// The result of asking an `InputStream` for a value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Input<T> {
+    Value(T),
+    /// Nothing is available yet, but there may be later.
+    WouldBlock,
+    /// Nothing more will ever be available.
+    Closed,
+}
+
+impl<T> From<Option<T>> for Input<T> {
+    /// `None` means the input is closed.
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Input::Closed, Input::Value)
+    }
+}
+
+pub trait InputStream {
+    type Item;
+    fn next_input(&mut self) -> Input<Self::Item>;
+}
+
+/// Input from an iterator, which is closed once the iterator ends.
+#[derive(Debug, Clone)]
+pub struct IterInput<I>(pub I);
+
+impl<I> InputStream for IterInput<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next_input(&mut self) -> Input<Self::Item> {
+        self.0.next().into()
+    }
+}
+
+/// More values may be pushed later, so an empty queue would block.
+impl<T> InputStream for VecDeque<T> {
+    type Item = T;
+
+    fn next_input(&mut self) -> Input<Self::Item> {
+        self.pop_front().map_or(Input::WouldBlock, Input::Value)
+    }
+}
+
+/// Never waits; an empty channel would block.
+impl<T> InputStream for Receiver<T> {
+    type Item = T;
+
+    fn next_input(&mut self) -> Input<Self::Item> {
+        match self.try_recv() {
+            Ok(v) => Input::Value(v),
+            Err(TryRecvError::Empty) => Input::WouldBlock,
+            Err(TryRecvError::Disconnected) => Input::Closed,
+        }
+    }
+}
+
+impl<F, T> InputStream for F
+where
+    F: FnMut() -> Input<T>,
+{
+    type Item = T;
+
+    fn next_input(&mut self) -> Input<Self::Item> {
+        self()
+    }
+}
+
+pub trait OutputStream {
+    type Item;
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed>;
+}
+
+impl<O> OutputStream for &'_ mut O
+where
+    O: OutputStream,
+{
+    type Item = O::Item;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        (**self).push(val)
+    }
+}
+
+impl<T> OutputStream for Vec<T> {
+    type Item = T;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        Vec::push(self, val);
+        Ok(())
+    }
+}
+
+impl<T> OutputStream for Sender<T> {
+    type Item = T;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        self.send(val).map_err(|_| OutputClosed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specifications_day_02() -> Result<()> {
+        let mut state = vec![1, 0, 0, 0, 99];
+        execute(&mut state, None)?;
+        assert_eq!(state, [2, 0, 0, 0, 99]);
+
+        let mut state = vec![2, 3, 0, 3, 99];
+        execute(&mut state, None)?;
+        assert_eq!(state, [2, 3, 0, 6, 99]);
+
+        let mut state = vec![2, 4, 4, 5, 99, 0];
+        execute(&mut state, None)?;
+        assert_eq!(state, [2, 4, 4, 5, 99, 9801]);
+
+        let mut state = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
+        execute(&mut state, None)?;
+        assert_eq!(state, [30, 1, 1, 4, 2, 5, 6, 0, 99]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn specifications_day_05() -> Result<()> {
+        let mut program = vec![1002, 4, 3, 4, 33];
+        execute(&mut program, None)?;
+        assert_eq!(program[4], 99);
+
+        let mut program = vec![3, 0, 4, 0, 99];
+        let output = execute(&mut program, Some(42))?;
+        assert_eq!(output, [42]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_instructions() -> Result<()> {
+        let mut program = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let output = execute(&mut program, Some(8))?;
+        assert_eq!(output, [1]);
+
+        let mut program = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+        let output = execute(&mut program, Some(8))?;
+        assert_eq!(output, [0]);
+
+        let mut program = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
+        let output = execute(&mut program, Some(7))?;
+        assert_eq!(output, [0]);
+
+        let mut program = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
+        let output = execute(&mut program, Some(7))?;
+        assert_eq!(output, [1]);
+
+        Ok(())
+    }
+
+    #[test]
     fn jump_instructions() -> Result<()> {
         let mut program = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
         let output = execute(&mut program, Some(0))?;
@@ -499,6 +1762,392 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn run_pauses_for_input_and_output() -> Result<()> {
+        let mut computer = Computer::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        assert_eq!(computer.run()?, Status::NeedsInput);
+        assert_eq!(computer.run()?, Status::NeedsInput);
+
+        computer.push_input(8);
+        assert_eq!(computer.run()?, Status::Output(1));
+        assert_eq!(computer.run()?, Status::Halted);
+        assert_eq!(computer.run()?, Status::Halted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_resumes_with_relative_base() -> Result<()> {
+        // Echoes each input doubled, forever, through a relative-base cell
+        let mut computer = Computer::new(vec![109, 20, 203, 0, 21202, 0, 2, 0, 204, 0, 1105, 1, 2]);
+
+        for i in 1..=3 {
+            assert_eq!(computer.run()?, Status::NeedsInput);
+            computer.push_input(i);
+            assert_eq!(computer.run()?, Status::Output(i * 2));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn step_describes_instruction() -> Result<()> {
+        let mut computer = Computer::new(vec![1002, 4, 3, 4, 33]);
+
+        let step = computer.step()?;
+        assert_eq!(
+            step,
+            Step {
+                pc: 0,
+                operation: Operation::Multiply(
+                    Parameter::Position(4),
+                    Parameter::Immediate(3),
+                    Parameter::Position(4)
+                ),
+                operands: vec![33, 3, 4],
+                write: Some((4, 99)),
+                next_pc: 4,
+                status: None,
+            }
+        );
+        assert_eq!(step.operation.opcode(), 2);
+        assert_eq!(
+            step.operation
+                .parameters()
+                .iter()
+                .map(Parameter::mode)
+                .collect::<Vec<_>>(),
+            [Mode::Position, Mode::Immediate, Mode::Position]
+        );
+
+        let step = computer.step()?;
+        assert_eq!(step.operation, Operation::Halt);
+        assert_eq!(step.next_pc, 4);
+        assert_eq!(step.status, Some(Status::Halted));
+
+        Ok(())
+    }
+
+    #[test]
+    fn step_waits_for_input() -> Result<()> {
+        let mut computer = Computer::new(vec![109, 5, 203, 1, 99]);
+        computer.step()?;
+        assert_eq!(computer.relative_base(), 5);
+
+        let step = computer.step()?;
+        assert_eq!(step.status, Some(Status::NeedsInput));
+        assert_eq!(step.operands, [6]);
+        assert_eq!(step.write, None);
+        assert_eq!(computer.pc(), 2);
+
+        computer.push_input(7);
+        let step = computer.step()?;
+        assert_eq!(step.write, Some((6, 7)));
+        assert_eq!(step.next_pc, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn step_reports_distant_destinations() -> Result<()> {
+        let distant = 1 << 63;
+        let mut memory = SparseMemory::new();
+        for (a, &v) in [1101, 1, 2, distant].iter().enumerate() {
+            memory.set(a, v).unwrap();
+        }
+        let mut computer = Computer::new(memory);
+
+        let step = computer.step()?;
+        assert_eq!(step.operands, [1, 2, distant]);
+        assert_eq!(step.write, Some((1 << 63, 3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_carry_pc_and_instruction() {
+        let error = |program: Program, input: Option<Byte>| {
+            execute(&mut program.clone(), input).expect_err("Program should fail")
+        };
+
+        assert_eq!(
+            error(vec![1101, 1, 1, 0, 42], None),
+            Error::UnknownOpcode {
+                pc: 4,
+                instruction: 42
+            }
+        );
+        assert_eq!(
+            error(vec![301, 0, 0, 0, 99], None),
+            Error::UnknownMode {
+                pc: 0,
+                instruction: 301,
+                mode: 3
+            }
+        );
+        assert_eq!(
+            error(vec![4, -1, 99], None),
+            Error::NegativeAddress {
+                pc: 0,
+                instruction: 4,
+                address: -1
+            }
+        );
+        assert_eq!(
+            error(vec![109, -5, 204, 1, 99], None),
+            Error::NegativeAddress {
+                pc: 0,
+                instruction: 109,
+                address: -5
+            }
+        );
+        assert_eq!(
+            error(vec![11101, 1, 1, 0, 99], None),
+            Error::WriteToImmediate {
+                pc: 0,
+                instruction: 11101
+            }
+        );
+        assert_eq!(
+            error(vec![3, 0, 3, 0, 99], Some(3)),
+            Error::InputExhausted {
+                pc: 2,
+                instruction: 3
+            }
+        );
+        assert_eq!(
+            error(vec![1101, 1, 1, 0], None),
+            Error::PcOutOfBounds {
+                pc: 4,
+                instruction: None
+            }
+        );
+        assert_eq!(
+            error(vec![1101, 1, 1, 0, 1, 0], None),
+            Error::PcOutOfBounds {
+                pc: 4,
+                instruction: Some(1)
+            }
+        );
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints_pause_run() {
+        let mut computer: Computer = Computer::new(vec![3, 100, 1001, 100, 5, 101, 4, 101, 99]);
+        computer.add_breakpoint(2);
+        computer.add_watchpoint(101, Access::ReadWrite);
+
+        let mut quiet = computer.clone();
+        let mut output = Vec::new();
+        quiet.execute(Some(1), &mut output).unwrap();
+        assert_eq!(output, [6]);
+
+        computer.push_input(1);
+        assert_eq!(computer.run(), Ok(Status::Breakpoint(2)));
+        computer.program[100] = 10;
+
+        let written = Status::Watchpoint {
+            pc: 2,
+            address: 101,
+            access: Access::Write,
+            value: 15,
+        };
+        assert_eq!(computer.run(), Ok(written));
+
+        // The output comes before the read that produced it
+        let read = Status::Watchpoint {
+            pc: 6,
+            address: 101,
+            access: Access::Read,
+            value: 15,
+        };
+        assert_eq!(computer.run(), Ok(Status::Output(15)));
+        assert_eq!(computer.run(), Ok(read));
+        assert_eq!(computer.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn reads_and_writes_of_one_instruction_are_both_reported() {
+        // add [5], #5, [5]
+        let mut computer: Computer = Computer::new(vec![1001, 5, 5, 5, 99, 1]);
+        computer.add_watchpoint(5, Access::ReadWrite);
+
+        let watched = |access, value| Status::Watchpoint {
+            pc: 0,
+            address: 5,
+            access,
+            value,
+        };
+        assert_eq!(computer.run(), Ok(watched(Access::Read, 1)));
+        assert_eq!(computer.run(), Ok(watched(Access::Write, 6)));
+        assert_eq!(computer.run(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn step_returns_a_queued_watchpoint() -> Result<()> {
+        let mut computer: Computer = Computer::new(vec![4, 3, 99, 7]);
+        computer.add_watchpoint(3, Access::Read);
+
+        let step = computer.step()?;
+        assert_eq!(step.status, Some(Status::Output(7)));
+
+        let step = computer.step()?;
+        assert_eq!(
+            step.status,
+            Some(Status::Watchpoint {
+                pc: 0,
+                address: 3,
+                access: Access::Read,
+                value: 7,
+            })
+        );
+        assert_eq!(step.next_pc, 2);
+
+        let step = computer.step()?;
+        assert_eq!(step.status, Some(Status::Halted));
+
+        Ok(())
+    }
+
+    #[test]
+    fn budgets_stop_runaway_programs() {
+        let mut computer: Computer = Computer::new(vec![1101, 1, 2, 5, 1105, 1, 4]);
+
+        computer.set_instruction_limit(Some(10));
+        assert_eq!(computer.run(), Ok(Status::BudgetExhausted));
+        assert_eq!(computer.instructions_retired(), 10);
+        assert_eq!(computer.pc(), 4);
+        assert_eq!(computer.program[5], 3);
+
+        computer.set_instruction_limit(Some(5));
+        assert_eq!(computer.run(), Ok(Status::BudgetExhausted));
+        assert_eq!(computer.instructions_retired(), 15);
+
+        computer.set_instruction_limit(None);
+        computer.set_deadline(Some(std::time::Instant::now()));
+        assert_eq!(computer.run(), Ok(Status::BudgetExhausted));
+
+        let error = computer.execute(None, Vec::new()).unwrap_err();
+        assert_eq!(
+            error,
+            Error::BudgetExhausted {
+                pc: 4,
+                instruction: 1105
+            }
+        );
+    }
+
+    #[test]
+    fn controller_can_stop_early() {
+        // Echoes its input forever
+        let echo: Program = vec![3, 7, 4, 7, 1105, 1, 0];
+
+        let mut computer = Computer::new(echo);
+        let (first, outcome) = computer.try_execute_side_by_side(|tx, rx| {
+            tx.send(5).unwrap();
+            rx.recv().unwrap()
+        });
+        assert_eq!(first, 5);
+        assert_eq!(outcome, Ok(Outcome::Cancelled));
+
+        // Outputs 1 forever
+        let mut computer: Computer = Computer::new(vec![104, 1, 1105, 1, 0]);
+        let (_, outcome) = computer.try_execute_side_by_side(|_, rx| rx.recv().unwrap());
+        assert_eq!(outcome, Ok(Outcome::Cancelled));
+
+        // Sending after the program halts is harmless
+        let mut computer: Computer = Computer::new(vec![3, 0, 99]);
+        let (_, outcome) = computer.try_execute_side_by_side(|tx, rx| {
+            tx.send(1).unwrap();
+            assert_eq!(rx.recv(), Err(crossbeam_channel::RecvError));
+            tx.send(2).unwrap();
+        });
+        assert_eq!(outcome, Ok(Outcome::Halted));
+    }
+
+    #[test]
+    fn input_streams_can_block() {
+        // Echoes its input forever
+        let echo: Program = vec![3, 7, 4, 7, 1105, 1, 0];
+        let exhausted = Err(Error::InputExhausted {
+            pc: 0,
+            instruction: 3,
+        });
+
+        let mut computer = Computer::new(echo.clone());
+        let mut queue: VecDeque<_> = vec![1, 2].into();
+        let mut output = Vec::new();
+        let status = computer.execute_until_blocked(&mut queue, &mut output);
+        assert_eq!(status, Ok(Status::NeedsInput));
+        assert_eq!(output, [1, 2]);
+
+        queue.push_back(3);
+        let status = computer.execute_until_blocked(&mut queue, &mut output);
+        assert_eq!(status, Ok(Status::NeedsInput));
+        assert_eq!(output, [1, 2, 3]);
+
+        // Computes each input on demand
+        let mut computer = Computer::new(echo.clone());
+        let mut n = 0;
+        let mut doubling = || {
+            n += 1;
+            if n <= 3 {
+                Input::Value(n * 2)
+            } else {
+                Input::Closed
+            }
+        };
+        let mut output = Vec::new();
+        let status = computer.execute_until_blocked(&mut doubling, &mut output);
+        assert_eq!(status, exhausted);
+        assert_eq!(output, [2, 4, 6]);
+
+        let mut computer = Computer::new(echo);
+        let (tx, mut rx) = channel();
+        tx.send(5).unwrap();
+        let mut output = Vec::new();
+        let status = computer.execute_until_blocked(&mut rx, &mut output);
+        assert_eq!(status, Ok(Status::NeedsInput));
+
+        drop(tx);
+        let status = computer.execute_until_blocked(&mut rx, &mut output);
+        assert_eq!(status, exhausted);
+        assert_eq!(output, [5]);
+    }
+
+    #[test]
+    fn sparse_memory_handles_distant_addresses() -> Result<()> {
+        let program = vec![1101, 7, 0, 1_000_000_000_000, 4, 1_000_000_000_000, 99];
+        let mut computer = Computer::new(SparseMemory::from(program));
+
+        let mut output = Output::new();
+        computer.execute(None, &mut output)?;
+        assert_eq!(output, [7]);
+        assert_eq!(computer.program.get(1_000_000_000_000), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_can_be_limited() {
+        let program: Program = vec![3, 8, 1101, 1, 1, 20_000_000, 99, 0, 0];
+        let mut computer = Computer::new(program);
+        computer.set_memory_limit(Some(4));
+        computer.push_input(7);
+
+        // Writes inside memory are fine, even past the limit
+        assert_eq!(
+            computer.execute(None, &mut Vec::new()),
+            Err(Error::AddressOutOfRange {
+                pc: 2,
+                instruction: 1101,
+                address: 20_000_000,
+            })
+        );
+        assert_eq!(computer.program[8], 7);
+        assert_eq!(computer.program.len(), 9);
+    }
+
     #[test]
     fn quine() -> Result<()> {
         let original = [
@@ -528,4 +2177,145 @@ mod tests {
         assert_eq!(output[0], 1125899906842624);
         Ok(())
     }
+
+    #[test]
+    fn sixty_four_bit_words() -> Result<(), Error<i64>> {
+        let original: Vec<i64> = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut computer = Computer::new(original.clone());
+        let mut output = Vec::new();
+        computer.execute(None, &mut output)?;
+        assert_eq!(output, original);
+
+        let mut computer: Computer<SparseMemory<i64>> =
+            Computer::new(vec![3, 9, 1002, 9, -2, 9, 4, 9, 99].into());
+        computer.execute(Some(21), &mut output)?;
+        assert_eq!(output.last(), Some(&-42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_beyond_memory_are_errors() {
+        let program: Program = vec![1101, 1, 1, usize::MAX as Byte, 99];
+        let expected = Error::AddressOutOfRange {
+            pc: 0,
+            instruction: 1101,
+            address: usize::MAX as Byte,
+        };
+
+        let mut computer = Computer::new(program.clone());
+        assert_eq!(computer.execute(None, &mut Vec::new()), Err(expected));
+
+        let mut computer = Computer::new(SparseMemory::from(program));
+        assert_eq!(computer.execute(None, &mut Vec::new()), Err(expected));
+
+        // Too far out for contiguous memory, but fine when sparse
+        let program: Program = vec![1101, 1, 1, 1 << 40, 99];
+        let mut computer = Computer::new(program.clone());
+        assert!(computer.execute(None, &mut Vec::new()).is_err());
+
+        let mut computer = Computer::new(SparseMemory::from(program));
+        assert_eq!(computer.execute(None, &mut Vec::new()), Ok(()));
+        assert_eq!(computer.program.get(1 << 40), 2);
+    }
+
+    #[test]
+    fn relative_addresses_near_the_limits() {
+        // The address is past the end of memory, which reads as zero
+        let mut computer: Computer<Vec<i64>> = Computer::new(vec![109, i64::MAX, 204, 1, 99]);
+        let mut output = Vec::new();
+        assert_eq!(computer.execute(None, &mut output), Ok(()));
+        assert_eq!(output, [0]);
+
+        // The address doesn't fit in a usize
+        let base = usize::MAX as Byte;
+        let mut computer: Computer = Computer::new(vec![109, base, 204, 1, 99]);
+        assert_eq!(
+            computer.execute(None, &mut Vec::new()),
+            Err(Error::AddressOutOfRange {
+                pc: 2,
+                instruction: 204,
+                address: base + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn decoding_every_instruction_gives_the_same_results() {
+        // Walks the relative base down to 0, reading just below it and
+        // growing memory above it, until the read falls below address 0
+        let program = assemble(
+            "
+                  arb  #20
+            loop: add  rb-1, #1, rb+50
+                  arb  #-1
+                  jt   #1, #loop
+            ",
+        )
+        .unwrap();
+        let run = |cached| {
+            let mut computer = Computer::new(program.clone());
+            computer.set_decode_cache(cached);
+            let result = computer.execute(None, &mut Vec::new());
+            let retired = computer.instructions_retired();
+            (result, computer.program, retired)
+        };
+
+        let (result, memory, retired) = run(true);
+        assert!(matches!(result, Err(Error::NegativeAddress { pc: 2, .. })));
+        assert_eq!(memory.len(), 71);
+        assert_eq!(retired, 61);
+        assert_eq!(run(false), (result, memory, retired));
+    }
+
+    #[test]
+    fn arithmetic_policy_controls_overflow() {
+        let program: Vec<i64> = vec![1, 0, 0, 0, 1002, 9, 3, 9, 104, i64::MAX, 99];
+        let run = |policy| {
+            let mut computer = Computer::new(program.clone());
+            computer.set_arithmetic_policy(policy);
+            let mut output = Vec::new();
+            computer.execute(None, &mut output).map(|()| output)
+        };
+
+        assert_eq!(
+            run(ArithmeticPolicy::Checked),
+            Err(Error::Overflow {
+                pc: 4,
+                instruction: 1002
+            })
+        );
+        assert_eq!(
+            run(ArithmeticPolicy::Wrapping),
+            Ok(vec![i64::MAX.wrapping_mul(3)])
+        );
+        assert_eq!(run(ArithmeticPolicy::Saturating), Ok(vec![i64::MAX]));
+    }
+
+    #[test]
+    fn word_type_is_chosen_when_parsing() {
+        let computer: Computer<Vec<i64>> = "1102,3,4,0,99".parse().unwrap();
+        assert_eq!(computer.program[0], 1102_i64);
+
+        let error = "1102,99999999999999999999,4,0,99".parse::<Computer<Vec<i64>>>();
+        assert_eq!(error.unwrap_err().offset, 5);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn arbitrary_precision() -> Result<(), Error<BigInt>> {
+        let mut computer: Computer<Vec<BigInt>> =
+            "1102,340282366920938463463374607431768211456,2,7,4,7,99,0"
+                .parse()
+                .unwrap();
+        let mut output = Vec::new();
+        computer.execute(None, &mut output)?;
+        assert_eq!(
+            output[0].to_string(),
+            "680564733841876926926749214863536422912"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,242 @@
+use crate::{Byte, ProgramCounter, Word};
+use std::{collections::HashMap, error, fmt};
+
+/// Returned by `Memory::set` for an address that the memory can't
+/// hold.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AddressTooLarge(pub ProgramCounter);
+
+impl fmt::Display for AddressTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "address {} is too large for this memory", self.0)
+    }
+}
+
+impl error::Error for AddressTooLarge {}
+
+/// Storage for a running program. Every address can be read; cells
+/// that have never been written hold zero.
+pub trait Memory {
+    type Word: Word;
+
+    /// One past the highest address that may hold a non-zero value.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, address: ProgramCounter) -> Self::Word;
+
+    fn set(&mut self, address: ProgramCounter, value: Self::Word) -> Result<(), AddressTooLarge>;
+
+    /// Forgets every cell from `len` on, so that undoing a write that
+    /// grew memory also undoes the growth.
+    fn truncate(&mut self, len: usize);
+
+    /// Every cell from address 0 up to `len`, when they are stored
+    /// together, so that the common case of reading and writing
+    /// existing cells can skip the checks that `get` and `set` make.
+    fn as_mut_slice(&mut self) -> Option<&mut [Self::Word]> {
+        None
+    }
+
+    /// Contiguous stretches of memory, in address order, that between
+    /// them hold every non-zero cell. Memory that can be sparse must
+    /// override this so that its gaps are left out.
+    fn regions(&self) -> Vec<(ProgramCounter, Vec<Self::Word>)> {
+        vec![(0, (0..self.len()).map(|a| self.get(a)).collect())]
+    }
+}
+
+/// Contiguous memory that grows to cover the highest address written.
+/// A write that needs more memory than can be allocated fails rather
+/// than aborting; `Computer::set_memory_limit` sets a tighter bound.
+impl<W> Memory for Vec<W>
+where
+    W: Word,
+{
+    type Word = W;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn get(&self, address: ProgramCounter) -> W {
+        self.as_slice()
+            .get(address)
+            .cloned()
+            .unwrap_or_else(W::zero)
+    }
+
+    fn set(&mut self, address: ProgramCounter, value: W) -> Result<(), AddressTooLarge> {
+        if self.len() <= address {
+            let len = address.checked_add(1).ok_or(AddressTooLarge(address))?;
+            self.try_reserve_exact(len - self.len())
+                .map_err(|_| AddressTooLarge(address))?;
+            self.resize(len, W::zero());
+        }
+        self[address] = value;
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn as_mut_slice(&mut self) -> Option<&mut [W]> {
+        Some(self)
+    }
+
+    fn regions(&self) -> Vec<(ProgramCounter, Vec<W>)> {
+        vec![(0, self.clone())]
+    }
+}
+
+const PAGE_SIZE: usize = 1024;
+
+/// Memory that only allocates the pages that have been written to, so
+/// programs can use far-flung addresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMemory<W = Byte> {
+    pages: HashMap<usize, Box<[W]>>,
+    len: usize,
+}
+
+impl<W> Default for SparseMemory<W> {
+    fn default() -> Self {
+        Self {
+            pages: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<W> SparseMemory<W>
+where
+    W: Word,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The addresses and contents of the allocated pages, in address order.
+    pub fn pages(&self) -> impl Iterator<Item = (ProgramCounter, &[W])> {
+        let mut pages: Vec<_> = self.pages.iter().collect();
+        pages.sort_by_key(|&(&p, _)| p);
+        pages
+            .into_iter()
+            .map(|(&p, page)| (p * PAGE_SIZE, &page[..]))
+    }
+}
+
+impl<W> From<Vec<W>> for SparseMemory<W>
+where
+    W: Word,
+{
+    fn from(program: Vec<W>) -> Self {
+        let mut memory = Self::new();
+        for (address, value) in program.into_iter().enumerate() {
+            memory
+                .set(address, value)
+                .expect("Every address of a Vec fits");
+        }
+        memory
+    }
+}
+
+impl<W> Memory for SparseMemory<W>
+where
+    W: Word,
+{
+    type Word = W;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, address: ProgramCounter) -> W {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map_or_else(W::zero, |page| page[address % PAGE_SIZE].clone())
+    }
+
+    fn set(&mut self, address: ProgramCounter, value: W) -> Result<(), AddressTooLarge> {
+        // The length is one past the address
+        let len = address.checked_add(1).ok_or(AddressTooLarge(address))?;
+        let page = self
+            .pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| vec![W::zero(); PAGE_SIZE].into_boxed_slice());
+        page[address % PAGE_SIZE] = value;
+        self.len = self.len.max(len);
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.pages.retain(|&p, _| p * PAGE_SIZE < len);
+        if let Some(page) = self.pages.get_mut(&(len / PAGE_SIZE)) {
+            for cell in &mut page[len % PAGE_SIZE..] {
+                *cell = W::zero();
+            }
+        }
+        self.len = len;
+    }
+
+    fn regions(&self) -> Vec<(ProgramCounter, Vec<W>)> {
+        self.pages().map(|(a, page)| (a, page.to_vec())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparse_memory_reads_zero_when_unwritten() {
+        let mut memory = SparseMemory::<Byte>::from(vec![1, 2, 3]);
+        assert_eq!(memory.len(), 3);
+        assert_eq!(memory.get(1), 2);
+        assert_eq!(memory.get(5000), 0);
+
+        memory.set(1_000_000_000_000, 42).unwrap();
+        assert_eq!(memory.get(1_000_000_000_000), 42);
+        assert_eq!(memory.len(), 1_000_000_000_001);
+        assert_eq!(memory.pages().count(), 2);
+    }
+
+    #[test]
+    fn writes_that_cannot_be_held_fail() {
+        let mut memory = SparseMemory::<Byte>::new();
+        assert_eq!(memory.set(usize::MAX, 1), Err(AddressTooLarge(usize::MAX)));
+        assert_eq!(memory.len(), 0);
+
+        // Too many bytes to ever allocate
+        let mut memory: Vec<Byte> = vec![1, 2, 3];
+        assert_eq!(memory.set(usize::MAX, 1), Err(AddressTooLarge(usize::MAX)));
+        assert_eq!(memory.set(1 << 60, 1), Err(AddressTooLarge(1 << 60)));
+        assert_eq!(memory.len(), 3);
+        assert_eq!(memory.set(10, 1), Ok(()));
+        assert_eq!(memory.len(), 11);
+    }
+
+    #[test]
+    fn truncating_sparse_memory_forgets_the_tail() {
+        let mut memory = SparseMemory::<Byte>::new();
+        memory.set(3, 1).unwrap();
+        memory.set(5_000, 2).unwrap();
+        memory.truncate(4);
+        assert_eq!(memory.len(), 4);
+        assert_eq!(memory.get(3), 1);
+        assert_eq!(memory.pages().count(), 1);
+
+        // Growing again doesn't bring back what was forgotten
+        memory.set(9, 3).unwrap();
+        assert_eq!(memory.get(5_000), 0);
+        let cells: Vec<_> = (0..memory.len()).map(|a| memory.get(a)).collect();
+        assert_eq!(cells, [0, 0, 0, 1, 0, 0, 0, 0, 0, 3]);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{Byte, Computer, Error, Io, Memory, Program, Word};
+use std::collections::VecDeque;
+
+/// The address that puzzles conventionally reserve for the NAT.
+pub const NAT: Byte = 255;
+
+/// A message between computers, sent as three consecutive outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet<W = Byte> {
+    pub destination: W,
+    pub x: W,
+    pub y: W,
+}
+
+/// What the monitor is told about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<W = Byte> {
+    /// A packet addressed to something other than a computer in the
+    /// network, such as the `NAT`.
+    Packet(Packet<W>),
+    /// Every queue is empty and no computer sent anything during a
+    /// full round.
+    Idle,
+}
+
+/// What the monitor wants to happen next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control<T, W = Byte> {
+    Continue,
+    Send(Packet<W>),
+    Stop(T),
+}
+
+#[derive(Debug, Clone)]
+struct Node<M: Memory> {
+    computer: Computer<M>,
+    queue: VecDeque<M::Word>,
+    partial: Vec<M::Word>,
+    halted: bool,
+}
+
+/// Computers that talk to each other by sending packets. Each
+/// computer is first given its address as input; afterwards it reads
+/// `x` then `y` of each packet sent to it, or the default input when
+/// nothing is waiting.
+///
+/// The computers take turns on the current thread: each turn runs
+/// one computer until it asks for its next input, or for a bounded
+/// number of instructions, so the network behaves the same way every
+/// time.
+#[derive(Debug, Clone)]
+pub struct Network<M: Memory = Program> {
+    nodes: Vec<Node<M>>,
+    default_input: M::Word,
+}
+
+impl<M> Network<M>
+where
+    M: Memory + Clone,
+{
+    /// Boots `size` copies of `program`, at addresses `0..size`.
+    pub fn new(program: &M, size: usize) -> Self {
+        let nodes = (0..size)
+            .map(|address| {
+                let mut computer = Computer::new(program.clone());
+                computer.push_input(M::Word::from_i64(address as i64));
+                Node {
+                    computer,
+                    queue: VecDeque::new(),
+                    partial: Vec::new(),
+                    halted: false,
+                }
+            })
+            .collect();
+
+        Self {
+            nodes,
+            default_input: M::Word::from_i64(-1),
+        }
+    }
+}
+
+impl<M> Network<M>
+where
+    M: Memory,
+{
+    /// The value a computer reads when no packet is waiting for it.
+    /// This is -1 unless changed.
+    pub fn set_default_input(&mut self, value: M::Word) {
+        self.default_input = value;
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn computer(&self, address: usize) -> Option<&Computer<M>> {
+        self.nodes.get(address).map(|n| &n.computer)
+    }
+
+    pub fn computer_mut(&mut self, address: usize) -> Option<&mut Computer<M>> {
+        self.nodes.get_mut(address).map(|n| &mut n.computer)
+    }
+
+    /// Queues a packet for the computer it is addressed to. A packet
+    /// for any other address is handed back.
+    pub fn send(&mut self, packet: Packet<M::Word>) -> Option<Packet<M::Word>> {
+        let node = packet
+            .destination
+            .to_usize()
+            .and_then(|a| self.nodes.get_mut(a));
+
+        match node {
+            Some(node) => {
+                node.queue.push_back(packet.x);
+                node.queue.push_back(packet.y);
+                None
+            }
+            None => Some(packet),
+        }
+    }
+
+    /// Runs the network, passing each `Event` to `monitor`. Returns
+    /// the value the monitor stops with, or `None` once every computer
+    /// has halted.
+    pub fn run<T>(
+        &mut self,
+        mut monitor: impl FnMut(Event<M::Word>) -> Control<T, M::Word>,
+    ) -> Result<Option<T>, Error<M::Word>> {
+        let mut events = VecDeque::new();
+
+        loop {
+            if self.nodes.iter().all(|n| n.halted) {
+                return Ok(None);
+            }
+
+            let mut idle = true;
+            for address in 0..self.nodes.len() {
+                let (waited, sent) = self.turn(address)?;
+                idle &= waited && sent.is_empty();
+
+                events.extend(
+                    sent.into_iter()
+                        .filter_map(|p| self.send(p))
+                        .map(Event::Packet),
+                );
+            }
+
+            let running = self.nodes.iter().any(|n| !n.halted);
+            if idle && running && self.nodes.iter().all(|n| n.queue.is_empty()) {
+                events.push_back(Event::Idle);
+            }
+
+            while let Some(event) = events.pop_front() {
+                match monitor(event) {
+                    Control::Continue => {}
+                    Control::Send(packet) => {
+                        events.extend(self.send(packet).map(Event::Packet));
+                    }
+                    Control::Stop(value) => return Ok(Some(value)),
+                }
+            }
+        }
+    }
+
+    /// Runs one computer until it asks for input a second time, giving
+    /// it one queued value (or the default) the first time, or until
+    /// it has executed `TURN_LENGTH` instructions. Returns whether it
+    /// waited for input with nothing to read, and any packets it sent.
+    #[allow(clippy::type_complexity)]
+    fn turn(&mut self, address: usize) -> Result<(bool, Vec<Packet<M::Word>>), Error<M::Word>> {
+        let default_input = &self.default_input;
+        let node = &mut self.nodes[address];
+        let mut sent = Vec::new();
+
+        if node.halted {
+            return Ok((true, sent));
+        }
+
+        // Any limit the caller set still applies, and running out of
+        // it is still an error
+        let limit = node.computer.instruction_limit();
+        let turn_length = limit.map_or(TURN_LENGTH, |l| l.min(TURN_LENGTH));
+        let retired = node.computer.instructions_retired();
+
+        node.computer.set_instruction_limit(Some(turn_length));
+        let result = node.run_turn(default_input, &mut sent);
+        let used = node.computer.instructions_retired() - retired;
+        node.computer.set_instruction_limit(limit.map(|l| l - used));
+
+        match result {
+            Err(Error::BudgetExhausted { .. }) if limit != Some(used) => Ok((false, sent)),
+            result => result.map(|waited| (waited, sent)),
+        }
+    }
+}
+
+/// The most instructions a computer may execute in one turn, so that
+/// one that never reads still lets the others run.
+const TURN_LENGTH: u64 = 10_000;
+
+impl<M> Node<M>
+where
+    M: Memory,
+{
+    /// The body of `Network::turn`, adding packets to `sent`. Returns
+    /// whether the computer waited.
+    fn run_turn(
+        &mut self,
+        default_input: &M::Word,
+        sent: &mut Vec<Packet<M::Word>>,
+    ) -> Result<bool, Error<M::Word>> {
+        let mut waited = None;
+
+        loop {
+            match self.computer.run_until_io()? {
+                Io::NeedsInput => {
+                    if waited.is_some() {
+                        break;
+                    }
+                    let value = self.queue.pop_front();
+                    waited = Some(value.is_none());
+                    self.computer
+                        .push_input(value.unwrap_or_else(|| default_input.clone()));
+                }
+                Io::Output(v) => {
+                    self.partial.push(v);
+                    if self.partial.len() == 3 {
+                        let mut values = self.partial.drain(..);
+                        let mut next = || values.next().expect("Three values were sent");
+                        sent.push(Packet {
+                            destination: next(),
+                            x: next(),
+                            y: next(),
+                        });
+                    }
+                }
+                Io::Halted => {
+                    self.halted = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(waited.unwrap_or(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Each computer adds one to `y` and passes the packet along to the
+    // next address; the last one sends it to the NAT. Computer 0
+    // starts things off.
+    const RELAY: &str = "
+               in   [addr]
+               add  [addr], #1, [dest]
+               eq   [addr], #2, [flag]
+               jf   [flag], #start
+               add  #255, #0, [dest]
+        start: jt   [addr], #wait
+               out  #1
+               out  #7
+               out  #0
+        wait:  in   [x]
+               eq   [x], #-1, [flag]
+               jt   [flag], #wait
+               in   [y]
+               add  [y], #1, [y]
+               out  [dest]
+               out  [x]
+               out  [y]
+               jt   #1, #wait
+        addr:  data 0
+        dest:  data 0
+        flag:  data 0
+        x:     data 0
+        y:     data 0
+    ";
+
+    #[test]
+    fn nat_restarts_an_idle_network() {
+        let program = assemble(RELAY).unwrap();
+        let mut network = Network::new(&program, 3);
+        let mut nat = None;
+        let mut idle = 0;
+
+        let result = network.run(|event| match event {
+            Event::Packet(packet) => {
+                assert_eq!(packet.destination, NAT);
+                nat = Some(packet);
+                Control::Continue
+            }
+            Event::Idle => {
+                idle += 1;
+                let packet = nat.take().expect("Idle without a NAT packet");
+                if packet.y >= 5 {
+                    Control::Stop(packet)
+                } else {
+                    Control::Send(Packet {
+                        destination: 0,
+                        ..packet
+                    })
+                }
+            }
+        });
+
+        let expected = Packet {
+            destination: NAT,
+            x: 7,
+            y: 5,
+        };
+        assert_eq!(result, Ok(Some(expected)));
+        assert_eq!(idle, 2);
+    }
+
+    #[test]
+    fn computers_that_never_read_still_take_turns() {
+        // Computer 0 sends to the NAT forever without reading; computer
+        // 1 reports its address once, then waits
+        let program = assemble(
+            "
+                   in   [addr]
+                   jt   [addr], #wait
+            loop:  out  #255
+                   out  #0
+                   out  #0
+                   jt   #1, #loop
+            wait:  out  #255
+                   out  [addr]
+                   out  #1
+                   in   [addr]
+                   jt   #1, #wait
+            addr:  data 0
+            ",
+        )
+        .unwrap();
+        let mut network = Network::new(&program, 2);
+
+        let result = network.run(|event| match event {
+            Event::Packet(Packet { x: 1, y, .. }) => Control::Stop(y),
+            _ => Control::Continue,
+        });
+
+        assert_eq!(result, Ok(Some(1)));
+    }
+
+    #[test]
+    fn instruction_limits_still_apply() {
+        let program = assemble("jt #1, #0").unwrap();
+        let mut network = Network::new(&program, 1);
+        network
+            .computer_mut(0)
+            .unwrap()
+            .set_instruction_limit(Some(25_000));
+
+        let result = network.run(|_| Control::<()>::Continue);
+
+        assert!(matches!(result, Err(Error::BudgetExhausted { pc: 0, .. })));
+        assert_eq!(network.computer(0).unwrap().instructions_retired(), 25_000);
+    }
+
+    #[test]
+    fn stops_when_every_computer_halts() {
+        // Reports its address to the NAT, then halts
+        let program = assemble("in [1]\nout #255\nout [1]\nout #0\nhlt").unwrap();
+        let mut network = Network::new(&program, 2);
+        let mut seen = Vec::new();
+
+        let result = network.run(|event| {
+            seen.push(event);
+            Control::<()>::Continue
+        });
+
+        assert_eq!(result, Ok(None));
+        assert_eq!(
+            seen,
+            [
+                Event::Packet(Packet {
+                    destination: 255,
+                    x: 0,
+                    y: 0
+                }),
+                Event::Packet(Packet {
+                    destination: 255,
+                    x: 1,
+                    y: 0
+                }),
+            ]
+        );
+    }
+}
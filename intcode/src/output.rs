@@ -0,0 +1,238 @@
+use crate::{Byte, OutputStream, Word};
+use std::{error, fmt, io, marker::PhantomData};
+
+/// Returned by an `OutputStream` that can't accept any more values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OutputClosed;
+
+impl fmt::Display for OutputClosed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Output is closed")
+    }
+}
+
+impl error::Error for OutputClosed {}
+
+/// Passes each value to a closure.
+pub struct FnOutput<F, T> {
+    f: F,
+    _item: PhantomData<fn(T)>,
+}
+
+impl<F, T> FnOutput<F, T>
+where
+    F: FnMut(T),
+{
+    pub fn new(f: F) -> Self {
+        Self {
+            f,
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<F, T> OutputStream for FnOutput<F, T>
+where
+    F: FnMut(T),
+{
+    type Item = T;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        (self.f)(val);
+        Ok(())
+    }
+}
+
+/// A tuple of values of the same type, which `Chunks` groups
+/// outputs into.
+pub trait Tuple {
+    type Item;
+    const LEN: usize;
+
+    /// Takes exactly `LEN` values.
+    fn from_values(values: impl Iterator<Item = Self::Item>) -> Self;
+}
+
+macro_rules! tuple {
+    ($len:expr; $($name:ident),*) => {
+        impl<T> Tuple for ($(tuple!(@item $name),)*) {
+            type Item = T;
+            const LEN: usize = $len;
+
+            fn from_values(mut values: impl Iterator<Item = T>) -> Self {
+                $(let $name = values.next().expect("Not enough values for the tuple");)*
+                ($($name,)*)
+            }
+        }
+    };
+    (@item $name:ident) => { T };
+}
+
+tuple!(2; a, b);
+tuple!(3; a, b, c);
+tuple!(4; a, b, c, d);
+
+/// Groups values into tuples, such as `(x, y, tile)`, and passes each
+/// complete tuple to a closure.
+pub struct Chunks<C: Tuple, F> {
+    f: F,
+    pending: Vec<C::Item>,
+}
+
+impl<C, F> Chunks<C, F>
+where
+    C: Tuple,
+    F: FnMut(C),
+{
+    pub fn new(f: F) -> Self {
+        Self {
+            f,
+            pending: Vec::with_capacity(C::LEN),
+        }
+    }
+
+    /// Values that don't yet make up a whole tuple.
+    pub fn pending(&self) -> &[C::Item] {
+        &self.pending
+    }
+}
+
+impl<C, F> OutputStream for Chunks<C, F>
+where
+    C: Tuple,
+    F: FnMut(C),
+{
+    type Item = C::Item;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        self.pending.push(val);
+        if self.pending.len() == C::LEN {
+            (self.f)(C::from_values(self.pending.drain(..)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Decimal,
+    Ascii,
+}
+
+/// Writes values as text. Once writing fails, the stream is closed and
+/// the error is kept for `into_inner`.
+pub struct Writer<O, T = Byte> {
+    writer: O,
+    format: Format,
+    error: Option<io::Error>,
+    _item: PhantomData<fn(T)>,
+}
+
+impl<O, T> Writer<O, T>
+where
+    O: io::Write,
+{
+    /// Writes each value in decimal on its own line.
+    pub fn decimal(writer: O) -> Self {
+        Self::new(writer, Format::Decimal)
+    }
+
+    /// Writes values in the ASCII range as characters. Any other value
+    /// is written in decimal on its own line.
+    pub fn ascii(writer: O) -> Self {
+        Self::new(writer, Format::Ascii)
+    }
+
+    fn new(writer: O, format: Format) -> Self {
+        Self {
+            writer,
+            format,
+            error: None,
+            _item: PhantomData,
+        }
+    }
+
+    /// Flushes the writer and returns it, or the first error.
+    pub fn into_inner(mut self) -> io::Result<O> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+}
+
+impl<O, T> OutputStream for Writer<O, T>
+where
+    O: io::Write,
+    T: Word,
+{
+    type Item = T;
+
+    fn push(&mut self, val: Self::Item) -> Result<(), OutputClosed> {
+        if self.error.is_some() {
+            return Err(OutputClosed);
+        }
+
+        let ascii = val.to_usize().filter(|&c| c < 0x80);
+        let result = match (self.format, ascii) {
+            (Format::Ascii, Some(c)) => self.writer.write_all(&[c as u8]),
+            _ => writeln!(self.writer, "{}", val),
+        };
+
+        result.map_err(|e| {
+            self.error = Some(e);
+            OutputClosed
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel, Computer, Error};
+
+    #[test]
+    fn chunks_group_outputs() {
+        let mut computer: Computer = "104,1,104,2,104,3,104,4,104,5,104,6,104,7,99"
+            .parse()
+            .unwrap();
+        let mut triples = Vec::new();
+        let mut chunks = Chunks::new(|t: (_, _, _)| triples.push(t));
+
+        computer.execute(None, &mut chunks).unwrap();
+        assert_eq!(chunks.pending(), [7]);
+        drop(chunks);
+        assert_eq!(triples, [(1, 2, 3), (4, 5, 6)]);
+    }
+
+    #[test]
+    fn writers_format_values() {
+        // Prints "Hi\n", then 1000
+        let program = "104,72,104,105,104,10,104,1000,99";
+
+        let mut computer: Computer = program.parse().unwrap();
+        let mut writer = Writer::ascii(Vec::new());
+        computer.execute(None, &mut writer).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), b"Hi\n1000\n");
+
+        let mut computer: Computer = program.parse().unwrap();
+        let mut writer = Writer::decimal(Vec::new());
+        computer.execute(None, &mut writer).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), b"72\n105\n10\n1000\n");
+    }
+
+    #[test]
+    fn closed_outputs_are_errors() {
+        let mut computer: Computer = "104,1,104,2,99".parse().unwrap();
+        let (tx, rx) = channel();
+        drop(rx);
+
+        assert_eq!(
+            computer.execute(None, tx),
+            Err(Error::OutputClosed {
+                pc: 0,
+                instruction: 104
+            })
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{Program, Word};
+use std::{error, fmt, fs, io, path::Path};
+
+/// A value in program text that isn't a valid word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Which value it is, counting from zero.
+    pub index: usize,
+    /// Where the value starts, in bytes from the start of the text.
+    pub offset: usize,
+    /// The value, without surrounding whitespace. Empty when two
+    /// commas have nothing between them.
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "missing value")?;
+        } else {
+            write!(f, "invalid value `{}`", self.token)?;
+        }
+        write!(f, " at index {} (byte {})", self.index, self.offset)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A failure while reading a program from a file or reader.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "unable to read program: {}", e),
+            LoadError::Parse(e) => write!(f, "unable to parse program: {}", e),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+/// Parses comma-separated values, such as a puzzle input. Whitespace,
+/// including newlines, may surround each value, and the last value
+/// may be followed by a comma.
+pub fn parse_words<W: Word>(s: &str) -> Result<Vec<W>, ParseError> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    let mut tokens = s.split(',').enumerate().peekable();
+
+    while let Some((index, raw)) = tokens.next() {
+        let start = offset + raw.len() - raw.trim_start().len();
+        offset += raw.len() + 1;
+        let token = raw.trim();
+
+        // Nothing after a trailing comma, or no values at all
+        if token.is_empty() && tokens.peek().is_none() {
+            break;
+        }
+
+        let value = token.parse().map_err(|_| ParseError {
+            index,
+            offset: start,
+            token: token.into(),
+        })?;
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+pub fn parse_program(s: &str) -> Result<Program, ParseError> {
+    parse_words(s)
+}
+
+pub fn read_program<W: Word>(mut reader: impl io::Read) -> Result<Vec<W>, LoadError> {
+    let mut s = String::new();
+    reader.read_to_string(&mut s)?;
+    Ok(parse_words(&s)?)
+}
+
+pub fn load_program<W: Word>(path: impl AsRef<Path>) -> Result<Vec<W>, LoadError> {
+    read_program(fs::File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_and_trailing_commas() {
+        assert_eq!(parse_program(" 1, 2 ,\n3,\n"), Ok(vec![1, 2, 3]));
+        assert_eq!(parse_program("99"), Ok(vec![99]));
+        assert_eq!(parse_program("\n"), Ok(vec![]));
+
+        let words: Vec<i64> = read_program("1,-2,3\n".as_bytes()).unwrap();
+        assert_eq!(words, [1, -2, 3]);
+    }
+
+    #[test]
+    fn bad_values_are_located() {
+        assert_eq!(
+            parse_program("1,2,\n 12a,4"),
+            Err(ParseError {
+                index: 2,
+                offset: 6,
+                token: "12a".into(),
+            })
+        );
+        assert_eq!(
+            parse_program("1,,2").unwrap_err().to_string(),
+            "missing value at index 1 (byte 2)"
+        );
+        assert_eq!(
+            parse_program("1 2").unwrap_err().to_string(),
+            "invalid value `1 2` at index 0 (byte 0)"
+        );
+        assert!(parse_words::<i64>("1,2,99999999999999999999").is_err());
+    }
+}
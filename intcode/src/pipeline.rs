@@ -0,0 +1,349 @@
+use crate::{channel, error::Fault, Computer, Error, Io, Memory, Program, Receiver, Sender, Word};
+use crossbeam_channel::{select, TryRecvError};
+use crossbeam_utils::thread;
+use std::sync::{Mutex, MutexGuard};
+
+/// Identifies a computer added to a `Pipeline`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+/// Computers whose outputs feed the inputs of others.
+///
+/// Every output of a computer is sent to each computer it is
+/// connected to, and a computer with several incoming connections
+/// reads their values in the order they arrive. Connections may form
+/// loops. Each computer runs on its own thread.
+#[derive(Debug, Clone)]
+pub struct Pipeline<M: Memory = Program> {
+    computers: Vec<Computer<M>>,
+    edges: Vec<(NodeId, NodeId)>,
+}
+
+impl<M: Memory> Default for Pipeline<M> {
+    fn default() -> Self {
+        Self {
+            computers: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<M> Pipeline<M>
+where
+    M: Memory,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, program: M) -> NodeId {
+        self.add_computer(Computer::new(program))
+    }
+
+    /// Adds a computer that has already been set up, such as one with
+    /// a different arithmetic policy.
+    pub fn add_computer(&mut self, computer: Computer<M>) -> NodeId {
+        self.computers.push(computer);
+        NodeId(self.computers.len() - 1)
+    }
+
+    /// Queues a value that `node` reads before anything sent to it,
+    /// such as an amplifier's phase setting.
+    pub fn input(&mut self, node: NodeId, value: M::Word) {
+        self.computers[node.0].push_input(value);
+    }
+
+    /// Sends every output of `from` to `to`.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.edges.push((from, to));
+    }
+
+    /// Connects each node to the next one.
+    pub fn series(&mut self, nodes: &[NodeId]) {
+        for pair in nodes.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+    }
+
+    /// Connects each node to the next one, and the last back to the
+    /// first.
+    pub fn feedback_loop(&mut self, nodes: &[NodeId]) {
+        self.series(nodes);
+        if let (Some(&first), Some(&last)) = (nodes.first(), nodes.last()) {
+            self.connect(last, first);
+        }
+    }
+
+    /// Runs every computer until it halts, returning everything that
+    /// each of `capture` output, in the same order.
+    ///
+    /// A computer that needs input once nothing can send it any more,
+    /// including when every computer still running is waiting for
+    /// input, fails with `Error::InputExhausted`. Values sent to a
+    /// computer that has already halted are dropped. When computers
+    /// fail, the first failure is returned.
+    pub fn run(self, capture: &[NodeId]) -> Result<Vec<Vec<M::Word>>, Error<M::Word>>
+    where
+        M: Send,
+    {
+        let Self { computers, edges } = self;
+
+        let (txs, rxs): (Vec<_>, Vec<_>) = computers.iter().map(|_| channel()).unzip();
+        let outputs: Vec<Vec<_>> = (0..computers.len())
+            .map(|i| {
+                edges
+                    .iter()
+                    .filter(|(from, _)| from.0 == i)
+                    .map(|(_, to)| txs[to.0].clone())
+                    .collect()
+            })
+            .collect();
+        // Only the computers may hold senders, so that a computer
+        // notices when all of its inputs have finished
+        drop(txs);
+
+        let (stop_tx, stop_rx) = channel();
+        let state = Mutex::new(State {
+            running: computers.len(),
+            waiting: 0,
+            queued: 0,
+            stop: Some(stop_tx),
+            first_error: None,
+        });
+
+        let results = thread::scope(|s| {
+            let handles: Vec<_> = computers
+                .into_iter()
+                .zip(rxs)
+                .zip(outputs)
+                .enumerate()
+                .map(|(i, ((computer, input), outputs))| {
+                    let node = Node {
+                        input,
+                        outputs,
+                        stop: stop_rx.clone(),
+                        state: &state,
+                        keep: capture.contains(&NodeId(i)),
+                    };
+                    s.spawn(move |_| node.drive(computer))
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Computer panicked"))
+                .collect::<Vec<_>>()
+        })
+        .expect("Unable to run pipeline");
+
+        let state = state.into_inner().expect("Pipeline state poisoned");
+        if let Some(e) = state.first_error {
+            return Err(e);
+        }
+
+        Ok(capture
+            .iter()
+            .map(|node| results[node.0].clone().unwrap_or_default())
+            .collect())
+    }
+}
+
+/// Shared by every computer, so that a pipeline where each running
+/// computer waits on another can be told apart from one where a value
+/// is still on its way.
+struct State<W> {
+    running: usize,
+    waiting: usize,
+    /// Values sent but not yet received.
+    queued: usize,
+    /// Dropped to wake every waiting computer once none can proceed.
+    stop: Option<Sender<()>>,
+    first_error: Option<Error<W>>,
+}
+
+impl<W> State<W> {
+    fn stop_if_stuck(&mut self) {
+        if self.waiting == self.running && self.queued == 0 {
+            self.stop = None;
+        }
+    }
+}
+
+struct Node<'a, W> {
+    input: Receiver<W>,
+    outputs: Vec<Sender<W>>,
+    stop: Receiver<()>,
+    state: &'a Mutex<State<W>>,
+    keep: bool,
+}
+
+impl<W> Node<'_, W>
+where
+    W: Word,
+{
+    fn lock(&self) -> MutexGuard<'_, State<W>> {
+        self.state.lock().expect("Pipeline state poisoned")
+    }
+
+    fn drive<M>(self, mut computer: Computer<M>) -> Option<Vec<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let result = self.run(&mut computer);
+        let Node { input, state, .. } = self;
+
+        let mut state = state.lock().expect("Pipeline state poisoned");
+        // Values still waiting for this computer will never be read, and
+        // later sends to it fail instead of being counted
+        state.queued -= input.try_iter().count();
+        drop(input);
+        state.running -= 1;
+        state.stop_if_stuck();
+
+        match result {
+            Ok(kept) => Some(kept),
+            Err(e) => {
+                state.first_error.get_or_insert(e);
+                // Nothing more will be returned, so the rest can stop
+                state.stop = None;
+                None
+            }
+        }
+    }
+
+    fn run<M>(&self, computer: &mut Computer<M>) -> Result<Vec<W>, Error<W>>
+    where
+        M: Memory<Word = W>,
+    {
+        let mut kept = Vec::new();
+
+        loop {
+            match computer.run_until_io()? {
+                Io::NeedsInput => match self.receive() {
+                    Some(v) => computer.push_input(v),
+                    None => return Err(Fault::InputExhausted.at(computer.pc(), &computer.program)),
+                },
+                Io::Output(v) => {
+                    let mut state = self.lock();
+                    for tx in &self.outputs {
+                        // The receiving computer may have halted
+                        if tx.send(v.clone()).is_ok() {
+                            state.queued += 1;
+                        }
+                    }
+                    drop(state);
+
+                    if self.keep {
+                        kept.push(v);
+                    }
+                }
+                Io::Halted => return Ok(kept),
+            }
+        }
+    }
+
+    /// Waits for the next value, or `None` once no computer can send
+    /// one.
+    fn receive(&self) -> Option<W> {
+        let mut state = self.lock();
+        match self.input.try_recv() {
+            Ok(v) => {
+                state.queued -= 1;
+                return Some(v);
+            }
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        state.waiting += 1;
+        state.stop_if_stuck();
+        drop(state);
+
+        let value = select! {
+            recv(self.input) -> v => v.ok(),
+            recv(self.stop) -> _ => None,
+        };
+
+        let mut state = self.lock();
+        state.waiting -= 1;
+        if value.is_some() {
+            state.queued -= 1;
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fan_out_and_merge() {
+        let double: Program = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        let add: Program = vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99, 0, 0];
+
+        // 5 -> double -> (double, double) -> add
+        let mut pipeline = Pipeline::new();
+        let source = pipeline.add(double.clone());
+        let left = pipeline.add(double.clone());
+        let right = pipeline.add(double);
+        let sum = pipeline.add(add);
+
+        pipeline.input(source, 5);
+        pipeline.connect(source, left);
+        pipeline.connect(source, right);
+        pipeline.connect(left, sum);
+        pipeline.connect(right, sum);
+
+        assert_eq!(pipeline.run(&[sum, source]), Ok(vec![vec![40], vec![10]]));
+    }
+
+    #[test]
+    fn missing_input_is_an_error() {
+        let mut pipeline: Pipeline = Pipeline::new();
+        let a = pipeline.add(vec![3, 0, 99]);
+
+        assert_eq!(
+            pipeline.run(&[a]),
+            Err(Error::InputExhausted {
+                pc: 0,
+                instruction: 3
+            })
+        );
+    }
+
+    #[test]
+    fn unseeded_loop_is_an_error() {
+        let echo: Program = vec![3, 0, 4, 0, 99];
+        let mut pipeline = Pipeline::new();
+        let a = pipeline.add(echo.clone());
+        let b = pipeline.add(echo);
+        pipeline.feedback_loop(&[a, b]);
+
+        assert_eq!(
+            pipeline.run(&[a]),
+            Err(Error::InputExhausted {
+                pc: 0,
+                instruction: 3
+            })
+        );
+    }
+
+    #[test]
+    fn the_first_failure_is_returned() {
+        // The second computer fails at once, while the first waits for
+        // a value that never comes and fails afterwards
+        let mut pipeline: Pipeline = Pipeline::new();
+        let waits = pipeline.add(vec![3, 0, 99]);
+        let fails = pipeline.add(vec![42]);
+        pipeline.connect(fails, waits);
+
+        assert_eq!(
+            pipeline.run(&[waits]),
+            Err(Error::UnknownOpcode {
+                pc: 0,
+                instruction: 42
+            })
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::ProgramCounter;
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+
+/// How many entries of each table the report shows.
+const REPORT_LIMIT: usize = 10;
+
+/// Execution counts gathered while a `Computer` runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    /// The total number of instructions executed.
+    pub instructions: u64,
+    pub by_pc: BTreeMap<ProgramCounter, u64>,
+    pub by_mnemonic: BTreeMap<&'static str, u64>,
+    pub reads: BTreeMap<ProgramCounter, u64>,
+    pub writes: BTreeMap<ProgramCounter, u64>,
+    /// Jumps to an earlier address, keyed by (target, source).
+    pub backward_jumps: BTreeMap<(ProgramCounter, ProgramCounter), u64>,
+}
+
+/// A stretch of code that a backward jump repeats.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub start: ProgramCounter,
+    /// The address of the jump back to `start`.
+    pub end: ProgramCounter,
+    pub iterations: u64,
+    /// Instructions executed between `start` and `end`, inclusive.
+    pub instructions: u64,
+}
+
+impl Profile {
+    /// Loops found from backward jumps, the most expensive first.
+    pub fn hot_loops(&self) -> Vec<Loop> {
+        let mut loops: Vec<_> = self
+            .backward_jumps
+            .iter()
+            .map(|(&(start, end), &iterations)| Loop {
+                start,
+                end,
+                iterations,
+                instructions: self.by_pc.range(start..=end).map(|(_, &n)| n).sum(),
+            })
+            .collect();
+        loops.sort_by_key(|l| (Reverse(l.instructions), l.start));
+        loops
+    }
+
+    pub(crate) fn count(map: &mut BTreeMap<ProgramCounter, u64>, address: ProgramCounter) {
+        *map.entry(address).or_insert(0) += 1;
+    }
+}
+
+fn busiest<K: Copy + Ord>(map: &BTreeMap<K, u64>) -> Vec<(K, u64)> {
+    let mut entries: Vec<_> = map.iter().map(|(&k, &n)| (k, n)).collect();
+    entries.sort_by_key(|&(k, n)| (Reverse(n), k));
+    entries.truncate(REPORT_LIMIT);
+    entries
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.instructions.max(1) as f64;
+        let share = |n: u64| 100.0 * n as f64 / total;
+
+        writeln!(f, "{} instructions retired", self.instructions)?;
+
+        writeln!(f, "\nHot loops:")?;
+        for l in self.hot_loops().iter().take(REPORT_LIMIT) {
+            writeln!(
+                f,
+                "  {:>5}..={:<5} {:>10} iterations {:>12} instructions ({:.1}%)",
+                l.start,
+                l.end,
+                l.iterations,
+                l.instructions,
+                share(l.instructions)
+            )?;
+        }
+
+        writeln!(f, "\nBy operation:")?;
+        for (m, n) in busiest(&self.by_mnemonic) {
+            writeln!(f, "  {:<5} {:>12} ({:.1}%)", m, n, share(n))?;
+        }
+
+        writeln!(f, "\nBusiest instructions:")?;
+        for (pc, n) in busiest(&self.by_pc) {
+            writeln!(f, "  {:>5} {:>12} ({:.1}%)", pc, n, share(n))?;
+        }
+
+        writeln!(f, "\nMost read addresses:")?;
+        for (a, n) in busiest(&self.reads) {
+            writeln!(f, "  {:>5} {:>12}", a, n)?;
+        }
+
+        writeln!(f, "\nMost written addresses:")?;
+        for (a, n) in busiest(&self.writes) {
+            writeln!(f, "  {:>5} {:>12}", a, n)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    #[test]
+    fn counts_a_loop() {
+        // Counts [100] down from 3, outputting each value
+        let mut computer: Computer = "101,-1,100,100,4,100,1005,100,0,99".parse().unwrap();
+        computer.program.resize(101, 0);
+        computer.program[100] = 3;
+        computer.start_profiling();
+
+        let mut output = Vec::new();
+        computer.execute(None, &mut output).unwrap();
+        assert_eq!(output, [2, 1, 0]);
+
+        let profile = computer.stop_profiling().unwrap();
+        assert_eq!(profile.instructions, 9);
+        assert_eq!(profile.by_pc[&0], 3);
+        assert_eq!(profile.by_mnemonic["jt"], 3);
+        assert_eq!(profile.reads[&100], 9);
+        assert_eq!(profile.writes[&100], 3);
+        assert_eq!(
+            profile.hot_loops(),
+            [Loop {
+                start: 0,
+                end: 6,
+                iterations: 2,
+                instructions: 9,
+            }]
+        );
+
+        let report = profile.to_string();
+        assert!(report.starts_with("9 instructions retired\n"));
+        assert!(report
+            .contains("      0..=6              2 iterations            9 instructions (100.0%)"));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{ArithmeticPolicy, Byte, ProgramCounter, Word};
+use itertools::Itertools;
+use std::{fmt, str::FromStr};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// Everything needed to resume a `Computer` later. Outputs are handed
+/// over as soon as they are produced, so only pending input is kept.
+/// Debugging state isn't kept either: breakpoints, watchpoints, and a
+/// watchpoint hit that is still queued behind another stop.
+///
+/// The text format is line based:
+///
+/// ```text
+/// intcode-snapshot 1
+/// pc 2
+/// relative-base 0
+/// arithmetic checked
+/// length 5
+/// input 7,8
+/// memory 0 3,0,4,0,99
+/// ```
+///
+/// with one `memory` line per region of memory, giving its start
+/// address and values. Cells not covered by a region are zero.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<W = Byte> {
+    pub pc: ProgramCounter,
+    pub relative_base: ProgramCounter,
+    pub arithmetic: ArithmeticPolicy,
+    pub input: Vec<W>,
+    /// The length of memory, which may extend past the last region.
+    pub length: usize,
+    pub memory: Vec<(ProgramCounter, Vec<W>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl<W> fmt::Display for Snapshot<W>
+where
+    W: Word,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arithmetic = match self.arithmetic {
+            ArithmeticPolicy::Checked => "checked",
+            ArithmeticPolicy::Wrapping => "wrapping",
+            ArithmeticPolicy::Saturating => "saturating",
+        };
+
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "pc {}", self.pc)?;
+        writeln!(f, "relative-base {}", self.relative_base)?;
+        writeln!(f, "arithmetic {}", arithmetic)?;
+        writeln!(f, "length {}", self.length)?;
+        writeln!(f, "input {}", self.input.iter().join(","))?;
+        for (start, values) in &self.memory {
+            writeln!(f, "memory {} {}", start, values.iter().join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W> FromStr for Snapshot<W>
+where
+    W: Word,
+{
+    type Err = SnapshotError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = (1..).zip(s.lines()).filter(|(_, l)| !l.trim().is_empty());
+
+        match lines.next() {
+            Some((_, l)) if l.trim() == HEADER => {}
+            Some((line, _)) => {
+                return Err(SnapshotError {
+                    line,
+                    message: format!("expected `{}`", HEADER),
+                })
+            }
+            None => {
+                return Err(SnapshotError {
+                    line: 1,
+                    message: "snapshot is empty".into(),
+                })
+            }
+        }
+
+        let mut fields = Fields::default();
+        let mut last_line = 1;
+        let mut region_lines = Vec::new();
+
+        for (line, text) in lines {
+            last_line = line;
+            fields
+                .parse_line(text.trim())
+                .map_err(|message| SnapshotError { line, message })?;
+            region_lines.resize(fields.memory.len(), line);
+        }
+
+        let missing = |key| SnapshotError {
+            line: last_line,
+            message: format!("`{}` is missing", key),
+        };
+
+        let snapshot = Snapshot {
+            pc: fields.pc.ok_or_else(|| missing("pc"))?,
+            relative_base: fields
+                .relative_base
+                .ok_or_else(|| missing("relative-base"))?,
+            arithmetic: fields.arithmetic.ok_or_else(|| missing("arithmetic"))?,
+            input: fields.input.ok_or_else(|| missing("input"))?,
+            length: fields.length.ok_or_else(|| missing("length"))?,
+            memory: fields.memory,
+        };
+
+        // Restoring such a region would leave memory longer than the
+        // snapshot says it is
+        let length = snapshot.length;
+        let past_the_end = snapshot
+            .memory
+            .iter()
+            .zip(region_lines)
+            .find(|((start, values), _)| start + values.len() > length);
+        if let Some(((start, _), line)) = past_the_end {
+            return Err(SnapshotError {
+                line,
+                message: format!("memory at {} runs past the length of {}", start, length),
+            });
+        }
+
+        Ok(snapshot)
+    }
+}
+
+struct Fields<W> {
+    pc: Option<ProgramCounter>,
+    relative_base: Option<ProgramCounter>,
+    arithmetic: Option<ArithmeticPolicy>,
+    length: Option<usize>,
+    input: Option<Vec<W>>,
+    memory: Vec<(ProgramCounter, Vec<W>)>,
+}
+
+impl<W> Default for Fields<W> {
+    fn default() -> Self {
+        Self {
+            pc: None,
+            relative_base: None,
+            arithmetic: None,
+            length: None,
+            input: None,
+            memory: Vec::new(),
+        }
+    }
+}
+
+impl<W> Fields<W>
+where
+    W: Word,
+{
+    fn parse_line(&mut self, text: &str) -> Result<(), String> {
+        let (key, rest) = split_first_word(text);
+
+        match key {
+            "pc" => set(&mut self.pc, key, parse_number(rest)?),
+            "relative-base" => set(&mut self.relative_base, key, parse_number(rest)?),
+            "length" => set(&mut self.length, key, parse_number(rest)?),
+            "arithmetic" => {
+                let policy = match rest {
+                    "checked" => ArithmeticPolicy::Checked,
+                    "wrapping" => ArithmeticPolicy::Wrapping,
+                    "saturating" => ArithmeticPolicy::Saturating,
+                    _ => return Err(format!("unknown arithmetic policy `{}`", rest)),
+                };
+                set(&mut self.arithmetic, key, policy)
+            }
+            "input" => set(&mut self.input, key, parse_values(rest)?),
+            "memory" => {
+                let (start, values) = split_first_word(rest);
+                if values.is_empty() {
+                    return Err("`memory` needs an address and values".into());
+                }
+                let start = parse_number(start)?;
+                let values = parse_values(values)?;
+                if start.checked_add(values.len()).is_none() {
+                    return Err(format!("memory at {} runs past the largest address", start));
+                }
+                self.memory.push((start, values));
+                Ok(())
+            }
+            _ => Err(format!("unknown key `{}`", key)),
+        }
+    }
+}
+
+fn split_first_word(s: &str) -> (&str, &str) {
+    match s.find(' ') {
+        Some(i) => (&s[..i], s[i..].trim()),
+        None => (s, ""),
+    }
+}
+
+fn set<T>(slot: &mut Option<T>, key: &str, value: T) -> Result<(), String> {
+    match slot.replace(value) {
+        Some(_) => Err(format!("`{}` is given more than once", key)),
+        None => Ok(()),
+    }
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number `{}`", s))
+}
+
+fn parse_values<W: Word>(s: &str) -> Result<Vec<W>, String> {
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    s.split(',')
+        .map(str::trim)
+        .map(|v| v.parse().map_err(|_| format!("invalid value `{}`", v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Computer, Memory, Program, SparseMemory, Status};
+
+    #[test]
+    fn resumes_from_text() {
+        let mut computer: Computer =
+            Computer::new(vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 1105, 1, 0]);
+        computer.push_input(7);
+        computer.push_input(8);
+        computer.set_arithmetic_policy(ArithmeticPolicy::Wrapping);
+        computer.step().unwrap();
+
+        let text = computer.snapshot().to_string();
+        let snapshot: Snapshot = text.parse().unwrap();
+        let mut resumed: Computer = Computer::restore(snapshot).unwrap();
+
+        assert_eq!(resumed.pc(), 2);
+        assert_eq!(resumed.arithmetic_policy(), ArithmeticPolicy::Wrapping);
+        assert_eq!(resumed.run(), Ok(Status::Output(15)));
+        assert_eq!(resumed.program[13..], [7, 8, 15]);
+    }
+
+    #[test]
+    fn sparse_memory_stays_sparse() {
+        let mut memory = SparseMemory::<i64>::new();
+        memory.set(0, 99).unwrap();
+        memory.set(1 << 40, 42).unwrap();
+        let computer = Computer::new(memory);
+
+        let text = computer.snapshot().to_string();
+        assert_eq!(
+            text,
+            "intcode-snapshot 1\n\
+             pc 0\n\
+             relative-base 0\n\
+             arithmetic checked\n\
+             length 1099511627777\n\
+             input \n\
+             memory 0 99\n\
+             memory 1099511627776 42\n"
+        );
+
+        let restored: Computer<SparseMemory<i64>> =
+            Computer::restore(text.parse().unwrap()).unwrap();
+        assert_eq!(restored.program, computer.program);
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        let error = |s: &str| s.parse::<Snapshot>().unwrap_err();
+
+        assert_eq!(error("").line, 1);
+        assert_eq!(error("snapshot").line, 1);
+        assert_eq!(error("intcode-snapshot 1\npc 0\npc 1").line, 3);
+        assert_eq!(error("intcode-snapshot 1\n\nsize 3").line, 3);
+        assert_eq!(
+            error("intcode-snapshot 1\nmemory 0 1,x").to_string(),
+            "line 2: invalid value `x`"
+        );
+        assert_eq!(
+            error("intcode-snapshot 1\npc 0").to_string(),
+            "line 2: `relative-base` is missing"
+        );
+    }
+
+    #[test]
+    fn regions_must_fit_in_memory() {
+        let error = |s: &str| s.parse::<Snapshot>().unwrap_err().to_string();
+        let fields = "pc 0\nrelative-base 0\narithmetic checked\ninput \n";
+
+        assert_eq!(
+            error("intcode-snapshot 1\nmemory 18446744073709551615 1"),
+            "line 2: memory at 18446744073709551615 runs past the largest address"
+        );
+        assert_eq!(
+            error(&format!(
+                "intcode-snapshot 1\n{}memory 0 1\nmemory 4 1,2\nlength 5",
+                fields
+            )),
+            "line 7: memory at 4 runs past the length of 5"
+        );
+
+        // Too long for `Vec` memory, but fine when sparse
+        let text = format!("intcode-snapshot 1\n{}length 1152921504606846976", fields);
+        let snapshot: Snapshot = text.parse().unwrap();
+        assert!(Computer::<Program>::restore(snapshot.clone()).is_err());
+        let restored: Computer<SparseMemory> = Computer::restore(snapshot).unwrap();
+        assert_eq!(restored.program.len(), 1 << 60);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_through_serde() {
+        let mut computer = Computer::new(vec![3, 0, 99]);
+        computer.push_input(5);
+        let snapshot = computer.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{error::Fault, Computer, Error, Io, Memory};
+use futures::{
+    stream::Stream,
+    task::{Context, Poll},
+};
+use std::pin::Pin;
+
+/// The outputs of a `Computer` fed from an input stream, created by
+/// `Computer::into_stream`.
+///
+/// The computer runs on whichever task polls this stream, so many of
+/// them can share a single thread. Polling only returns `Pending` when
+/// the program is waiting for input that hasn't arrived yet.
+///
+/// The stream ends when the program halts. Errors, including the
+/// input stream ending while the program wants more, are yielded once
+/// and end the stream.
+#[derive(Debug)]
+pub struct Outputs<M: Memory, S> {
+    computer: Computer<M>,
+    input: S,
+    finished: bool,
+}
+
+impl<M, S> Outputs<M, S>
+where
+    M: Memory,
+{
+    /// Gets the computer back, to inspect its memory or resume it.
+    pub fn into_inner(self) -> Computer<M> {
+        self.computer
+    }
+}
+
+impl<M, S> Stream for Outputs<M, S>
+where
+    M: Memory + Unpin,
+    M::Word: Unpin,
+    S: Stream<Item = M::Word> + Unpin,
+{
+    type Item = Result<M::Word, Error<M::Word>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.finished {
+            return Poll::Ready(None);
+        }
+
+        let computer = &mut this.computer;
+        let error = loop {
+            match computer.run_until_io() {
+                Ok(Io::NeedsInput) => match Pin::new(&mut this.input).poll_next(cx) {
+                    Poll::Ready(Some(v)) => computer.push_input(v),
+                    Poll::Ready(None) => {
+                        break Fault::InputExhausted.at(computer.pc(), &computer.program)
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                Ok(Io::Output(v)) => return Poll::Ready(Some(Ok(v))),
+                Ok(Io::Halted) => {
+                    this.finished = true;
+                    return Poll::Ready(None);
+                }
+                Err(e) => break e,
+            }
+        };
+
+        this.finished = true;
+        Poll::Ready(Some(Err(error)))
+    }
+}
+
+impl<M> Computer<M>
+where
+    M: Memory,
+{
+    /// Runs the program asynchronously, reading input from `input`
+    /// whenever it needs a value.
+    pub fn into_stream<S>(self, input: S) -> Outputs<M, S>
+    where
+        S: Stream<Item = M::Word> + Unpin,
+    {
+        Outputs {
+            computer: self,
+            input,
+            finished: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Computer, Error};
+    use futures::{
+        channel::mpsc,
+        executor::{block_on, LocalPool},
+        stream::{self, StreamExt},
+        task::LocalSpawnExt,
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn yields_outputs() {
+        let computer: Computer = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+        let outputs: Vec<_> = block_on(computer.into_stream(stream::iter(vec![8])).collect());
+        assert_eq!(outputs, [Ok(1)]);
+
+        let computer: Computer = "3,0,99".parse().unwrap();
+        let outputs: Vec<_> = block_on(computer.into_stream(stream::empty()).collect());
+        assert_eq!(
+            outputs,
+            [Err(Error::InputExhausted {
+                pc: 0,
+                instruction: 3
+            })]
+        );
+    }
+
+    #[test]
+    fn feedback_loop_on_one_thread() {
+        let program: Computer = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,\
+                                 -1,28,1005,28,6,99,0,0,5"
+            .parse()
+            .unwrap();
+        let phases = [9, 8, 7, 6, 5];
+
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            phases.iter().map(|_| mpsc::unbounded()).unzip();
+        for (tx, &phase) in senders.iter().zip(&phases) {
+            tx.unbounded_send(phase).unwrap();
+        }
+        senders[0].unbounded_send(0).unwrap();
+
+        let last = Rc::new(Cell::new(None));
+        let mut pool = LocalPool::new();
+
+        for (i, input) in receivers.into_iter().enumerate() {
+            let mut outputs = program.clone().into_stream(input);
+            let next = senders[(i + 1) % phases.len()].clone();
+            let last = Rc::clone(&last);
+            let is_last = i == phases.len() - 1;
+
+            pool.spawner()
+                .spawn_local(async move {
+                    while let Some(v) = outputs.next().await {
+                        let v = v.expect("Amplifier failed");
+                        if is_last {
+                            last.set(Some(v));
+                        }
+                        // The first amplifier has halted once the last one finishes
+                        next.unbounded_send(v).ok();
+                    }
+                })
+                .unwrap();
+        }
+        drop(senders);
+
+        pool.run();
+        assert_eq!(last.get(), Some(139629729));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{Operation, ProgramCounter, Word};
+use itertools::Itertools;
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+
+struct Sink {
+    writer: Box<dyn io::Write + Send>,
+    error: Option<io::Error>,
+}
+
+/// Writes one JSON object per executed instruction, one per line:
+///
+/// ```text
+/// {"pc":0,"operation":"mul [4], #3, [4]","operands":[33,3,4],"writes":[{"address":4,"value":99}],"relative_base":0}
+/// ```
+///
+/// `operands` holds the value of each parameter, except that the
+/// parameter being written to gives its address. `relative_base` is
+/// the value in effect while the instruction executed.
+///
+/// Clones of a computer share the tracer, so their records interleave.
+#[derive(Clone)]
+pub(crate) struct Tracer {
+    sink: Arc<Mutex<Sink>>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer").finish()
+    }
+}
+
+impl Tracer {
+    pub(crate) fn new(writer: impl io::Write + Send + 'static) -> Self {
+        Self {
+            sink: Arc::new(Mutex::new(Sink {
+                writer: Box::new(writer),
+                error: None,
+            })),
+        }
+    }
+
+    /// Once writing fails, nothing more is written and the error is
+    /// kept for `finish`.
+    pub(crate) fn record<W: Word>(
+        &self,
+        pc: ProgramCounter,
+        operation: &Operation<W>,
+        operands: &[W],
+        write: Option<(ProgramCounter, &W)>,
+        relative_base: ProgramCounter,
+    ) {
+        let mut sink = self.sink.lock().expect("Tracer lock poisoned");
+        if sink.error.is_some() {
+            return;
+        }
+
+        let writes = write
+            .map(|(address, value)| format!(r#"{{"address":{},"value":{}}}"#, address, value))
+            .into_iter()
+            .join(",");
+
+        let result = writeln!(
+            sink.writer,
+            r#"{{"pc":{},"operation":"{}","operands":[{}],"writes":[{}],"relative_base":{}}}"#,
+            pc,
+            operation,
+            operands.iter().join(","),
+            writes,
+            relative_base,
+        );
+        if let Err(e) = result {
+            sink.error = Some(e);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> io::Result<()> {
+        let mut sink = self.sink.lock().expect("Tracer lock poisoned");
+        match sink.error.take() {
+            Some(e) => Err(e),
+            None => sink.writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Computer;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn one_line_per_instruction() -> io::Result<()> {
+        let trace = Shared::default();
+        let mut computer: Computer = "109,10,21002,9,3,1,204,1,99,11".parse().unwrap();
+        computer.trace_to(trace.clone());
+
+        let mut output = Vec::new();
+        computer.execute(None, &mut output).unwrap();
+        computer.stop_tracing()?;
+        assert_eq!(output, [33]);
+
+        let text = String::from_utf8(trace.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"pc":0,"operation":"arb #10","operands":[10],"writes":[],"relative_base":0}"#,
+                r#"{"pc":2,"operation":"mul [9], #3, rb+1","operands":[11,3,11],"writes":[{"address":11,"value":33}],"relative_base":10}"#,
+                r#"{"pc":6,"operation":"out rb+1","operands":[33],"writes":[],"relative_base":10}"#,
+                r#"{"pc":8,"operation":"hlt","operands":[],"writes":[],"relative_base":10}"#,
+            ]
+        );
+
+        Ok(())
+    }
+
+    struct Broken;
+
+    impl io::Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_errors_are_reported_when_stopping() {
+        let mut computer: Computer = "104,1,99".parse().unwrap();
+        computer.trace_to(Broken);
+        computer.execute(None, Vec::new()).unwrap();
+
+        let error = computer.stop_tracing().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{convert::TryFrom, fmt, hash::Hash, str::FromStr};
+
+/// The contents of a single memory cell.
+pub trait Word:
+    Clone + fmt::Debug + fmt::Display + Ord + Hash + FromStr + Send + Sync + 'static
+{
+    fn from_i64(v: i64) -> Self;
+
+    /// Saturates at the limits of the word, for reporting addresses
+    /// that don't fit in it.
+    fn from_i128(v: i128) -> Self;
+
+    fn to_usize(&self) -> Option<usize>;
+
+    fn to_isize(&self) -> Option<isize>;
+
+    /// The value modulo 10⁵, keeping its sign. This covers the opcode
+    /// and the modes of up to three parameters.
+    fn instruction_digits(&self) -> i64;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn wrapping_add(&self, other: &Self) -> Self;
+
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    fn saturating_add(&self, other: &Self) -> Self;
+
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    fn zero() -> Self {
+        Self::from_i64(0)
+    }
+}
+
+/// How `Add` and `Multiply` behave when the result doesn't fit in
+/// the word.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArithmeticPolicy {
+    /// Stop with an `Error::Overflow` at the offending instruction.
+    #[default]
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+impl ArithmeticPolicy {
+    /// Returns `None` only when checking and the sum overflows.
+    pub fn add<W: Word>(self, l: &W, r: &W) -> Option<W> {
+        match self {
+            ArithmeticPolicy::Checked => l.checked_add(r),
+            ArithmeticPolicy::Wrapping => Some(l.wrapping_add(r)),
+            ArithmeticPolicy::Saturating => Some(l.saturating_add(r)),
+        }
+    }
+
+    /// Returns `None` only when checking and the product overflows.
+    pub fn mul<W: Word>(self, l: &W, r: &W) -> Option<W> {
+        match self {
+            ArithmeticPolicy::Checked => l.checked_mul(r),
+            ArithmeticPolicy::Wrapping => Some(l.wrapping_mul(r)),
+            ArithmeticPolicy::Saturating => Some(l.saturating_mul(r)),
+        }
+    }
+}
+
+macro_rules! primitive_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn from_i64(v: i64) -> Self {
+                    v as $t
+                }
+
+                fn from_i128(v: i128) -> Self {
+                    <$t>::try_from(v).unwrap_or(if v < 0 { <$t>::MIN } else { <$t>::MAX })
+                }
+
+                fn to_usize(&self) -> Option<usize> {
+                    usize::try_from(*self).ok()
+                }
+
+                fn to_isize(&self) -> Option<isize> {
+                    isize::try_from(*self).ok()
+                }
+
+                fn instruction_digits(&self) -> i64 {
+                    (*self % 100_000) as i64
+                }
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_add(*self, *other)
+                }
+
+                fn checked_mul(&self, other: &Self) -> Option<Self> {
+                    <$t>::checked_mul(*self, *other)
+                }
+
+                fn wrapping_add(&self, other: &Self) -> Self {
+                    <$t>::wrapping_add(*self, *other)
+                }
+
+                fn wrapping_mul(&self, other: &Self) -> Self {
+                    <$t>::wrapping_mul(*self, *other)
+                }
+
+                fn saturating_add(&self, other: &Self) -> Self {
+                    <$t>::saturating_add(*self, *other)
+                }
+
+                fn saturating_mul(&self, other: &Self) -> Self {
+                    <$t>::saturating_mul(*self, *other)
+                }
+            }
+        )*
+    };
+}
+
+primitive_word!(i64, i128);
+
+#[cfg(feature = "bigint")]
+mod bigint {
+    use super::Word;
+    use num_bigint::BigInt;
+    use num_traits::ToPrimitive;
+
+    impl Word for BigInt {
+        fn from_i64(v: i64) -> Self {
+            BigInt::from(v)
+        }
+
+        fn from_i128(v: i128) -> Self {
+            BigInt::from(v)
+        }
+
+        fn to_usize(&self) -> Option<usize> {
+            ToPrimitive::to_usize(self)
+        }
+
+        fn to_isize(&self) -> Option<isize> {
+            ToPrimitive::to_isize(self)
+        }
+
+        fn instruction_digits(&self) -> i64 {
+            (self % BigInt::from(100_000))
+                .to_i64()
+                .expect("Remainder always fits")
+        }
+
+        // There is no overflow, so every policy gives the exact answer
+
+        fn checked_add(&self, other: &Self) -> Option<Self> {
+            Some(self + other)
+        }
+
+        fn checked_mul(&self, other: &Self) -> Option<Self> {
+            Some(self * other)
+        }
+
+        fn wrapping_add(&self, other: &Self) -> Self {
+            self + other
+        }
+
+        fn wrapping_mul(&self, other: &Self) -> Self {
+            self * other
+        }
+
+        fn saturating_add(&self, other: &Self) -> Self {
+            self + other
+        }
+
+        fn saturating_mul(&self, other: &Self) -> Self {
+            self * other
+        }
+    }
+}